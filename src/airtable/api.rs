@@ -1,25 +1,62 @@
-use std::{collections::HashMap, fmt::Display};
+use std::{collections::HashMap, fmt::Display, str::FromStr, time::Instant};
 
 use chrono::{DateTime, Utc};
 use reqwest::StatusCode;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
 use thiserror::Error;
+use tracing::Instrument;
 use url::Url;
 
+use super::{
+    cache::{self, CacheKey},
+    metrics,
+};
+
 const AIRTABLE_API_BASE: &str = "https://api.airtable.com/v0";
 
-#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+/// ID of a record, such as `recAbCdEfGh012345`.
+///
+/// Parsing checks the format, so malformed IDs are rejected
+/// before they are sent to Airtable.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(try_from = "String")]
 pub struct RecordId(String);
 
+/// Record IDs are `rec` followed by this many alphanumeric characters.
+const RECORD_ID_LENGTH: usize = 14;
+
+#[derive(Debug, Clone, Error)]
+#[error("`{0}` is not a valid record ID")]
+pub struct InvalidRecordId(String);
+
 impl Display for RecordId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.0)
     }
 }
 
-impl From<String> for RecordId {
-    fn from(value: String) -> Self {
-        RecordId(value)
+impl FromStr for RecordId {
+    type Err = InvalidRecordId;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        RecordId::try_from(s.to_owned())
+    }
+}
+
+impl TryFrom<String> for RecordId {
+    type Error = InvalidRecordId;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let valid = value.strip_prefix("rec").is_some_and(|rest| {
+            rest.len() == RECORD_ID_LENGTH && rest.chars().all(|c| c.is_ascii_alphanumeric())
+        });
+
+        if valid {
+            Ok(RecordId(value))
+        } else {
+            Err(InvalidRecordId(value))
+        }
     }
 }
 
@@ -42,6 +79,47 @@ pub struct ListRecords {
     sort: Option<Sort>,
     filter_by_formula: Option<String>,
     fields: Option<Vec<String>>,
+    /// The number of records returned in each request.
+    /// Must be less than or equal to `100`, which is the default.
+    page_size: Option<usize>,
+    /// Continue listing from the `offset` returned with a previous page.
+    /// Every other parameter has to be the same as in the request that returned it.
+    offset: Option<String>,
+    /// Serve the results from the shared [`cache`](super::cache::cache) when it's enabled.
+    cache: bool,
+}
+
+/// A single page of records, see [`ListRecords::request_page`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub records: Vec<Record<T>>,
+    /// Where the next page starts, `None` if this was the last one.
+    pub offset: Option<String>,
+}
+
+impl Page<Value> {
+    /// Converts raw fields, as stored in the cache, into `T`.
+    fn deserialize<T>(self) -> Result<Page<T>, serde_json::Error>
+    where
+        T: DeserializeOwned,
+    {
+        let records = self
+            .records
+            .into_iter()
+            .map(|rec| {
+                Ok(Record {
+                    id: rec.id,
+                    created_time: rec.created_time,
+                    fields: serde_json::from_value(rec.fields)?,
+                })
+            })
+            .collect::<Result<_, serde_json::Error>>()?;
+
+        Ok(Page {
+            records,
+            offset: self.offset,
+        })
+    }
 }
 
 impl ListRecords {
@@ -54,6 +132,9 @@ impl ListRecords {
             sort: None,
             filter_by_formula: None,
             fields: None,
+            page_size: None,
+            offset: None,
+            cache: false,
         }
     }
 
@@ -107,19 +188,46 @@ impl ListRecords {
         self
     }
 
-    pub async fn request<T>(self, key: &str) -> Result<Vec<Record<T>>, ApiError>
-    where
-        T: DeserializeOwned,
-    {
+    pub fn page_size(&mut self, size: usize) -> &mut Self {
+        self.page_size = Some(size);
+        self
+    }
+
+    pub fn with_page_size(mut self, size: usize) -> Self {
+        self.page_size(size);
+        self
+    }
+
+    pub fn offset(&mut self, offset: String) -> &mut Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    pub fn with_offset(mut self, offset: String) -> Self {
+        self.offset(offset);
+        self
+    }
+
+    pub fn cache(&mut self, cache: bool) -> &mut Self {
+        self.cache = cache;
+        self
+    }
+
+    pub fn with_cache(mut self, cache: bool) -> Self {
+        self.cache(cache);
+        self
+    }
+
+    fn url(&self) -> Result<Url, ApiError> {
         // create formatted base url for the given base and table
         let mut url = Url::parse(&format!("{AIRTABLE_API_BASE}/{}/{}", self.base, self.table))?;
         let mut pairs = url.query_pairs_mut();
 
-        if let Some(view) = self.view {
-            pairs.append_pair("view", &view);
+        if let Some(view) = &self.view {
+            pairs.append_pair("view", view);
         }
 
-        if let Some(sort) = self.sort {
+        if let Some(sort) = &self.sort {
             pairs.append_pair("sort[0][field]", &sort.field);
             pairs.append_pair(
                 "sort[0][direction]",
@@ -130,22 +238,146 @@ impl ListRecords {
             );
         }
 
-        if let Some(formula) = self.filter_by_formula {
-            pairs.append_pair("filterByFormula", &formula);
+        if let Some(formula) = &self.filter_by_formula {
+            pairs.append_pair("filterByFormula", formula);
         }
 
-        if let Some(fields) = self.fields {
+        if let Some(fields) = &self.fields {
             for field in fields {
-                pairs.append_pair("fields[]", &field);
+                pairs.append_pair("fields[]", field);
             }
         }
 
+        if let Some(size) = self.page_size {
+            pairs.append_pair("pageSize", &size.to_string());
+        }
+
         // get rid of `pairs` so we can use `url` later
         std::mem::drop(pairs);
 
+        Ok(url)
+    }
+
+    async fn fetch<T>(
+        client: &reqwest::Client,
+        table: &str,
+        endpoint: Url,
+        key: &str,
+    ) -> Result<ListResponse<T>, ApiError>
+    where
+        T: DeserializeOwned,
+    {
+        let res = send(
+            ApiRequest::ListRecords,
+            Some(table),
+            client.get(endpoint.to_string()),
+            key,
+        )
+        .await?;
+
+        Ok(res.json().await?)
+    }
+
+    fn cache_key(&self, single_page: bool) -> Result<CacheKey, ApiError> {
+        Ok(CacheKey {
+            base: self.base.clone(),
+            table: self.table.clone(),
+            query: self.url()?.query().unwrap_or_default().to_owned(),
+            max_records: self.max_records,
+            offset: self.offset.clone(),
+            single_page,
+        })
+    }
+
+    /// Looks the request up in the cache, or fetches and caches it on a miss.
+    async fn cached<T, F>(self, single_page: bool, fetch: F) -> Result<Page<T>, ApiError>
+    where
+        T: DeserializeOwned,
+        F: AsyncFnOnce(Self) -> Result<Page<Value>, ApiError>,
+    {
+        let cache = cache::cache();
+        let key = self.cache_key(single_page)?;
+
+        let page = match cache.get(&key) {
+            Some(page) => page,
+            None => {
+                let generation = cache.generation(&key.base, &key.table);
+                let page = fetch(self).await?;
+                cache.insert(key, generation, page.clone());
+                page
+            }
+        };
+
+        Ok(page.deserialize()?)
+    }
+
+    /// Requests a single page of records, starting at [`ListRecords::offset`] if set.
+    pub async fn request_page<T>(self, key: &str) -> Result<Page<T>, ApiError>
+    where
+        T: DeserializeOwned,
+    {
+        if self.cache && cache::cache().enabled() {
+            return self
+                .cached(true, async |request| request.fetch_page(key).await)
+                .await;
+        }
+
+        self.fetch_page(key).await
+    }
+
+    async fn fetch_page<T>(self, key: &str) -> Result<Page<T>, ApiError>
+    where
+        T: DeserializeOwned,
+    {
+        let mut endpoint = self.url()?;
+
+        if let Some(max) = self.max_records {
+            endpoint
+                .query_pairs_mut()
+                .append_pair("maxRecords", &max.to_string());
+        }
+
+        if let Some(off) = self.offset {
+            endpoint.query_pairs_mut().append_pair("offset", &off);
+        }
+
+        let content: ListResponse<T> =
+            Self::fetch(&reqwest::Client::new(), &self.table, endpoint, key).await?;
+
+        Ok(Page {
+            records: content.records,
+            offset: content.offset,
+        })
+    }
+
+    pub async fn request<T>(self, key: &str) -> Result<Vec<Record<T>>, ApiError>
+    where
+        T: DeserializeOwned,
+    {
+        if self.cache && cache::cache().enabled() {
+            let page = self
+                .cached(false, async |request| {
+                    Ok(Page {
+                        records: request.fetch_all(key).await?,
+                        offset: None,
+                    })
+                })
+                .await?;
+            return Ok(page.records);
+        }
+
+        self.fetch_all(key).await
+    }
+
+    async fn fetch_all<T>(self, key: &str) -> Result<Vec<Record<T>>, ApiError>
+    where
+        T: DeserializeOwned,
+    {
+        let url = self.url()?;
+
         let mut records = Vec::new();
         let mut max_records = self.max_records;
-        let mut offset: Option<String> = None;
+        let mut offset: Option<String> = self.offset;
         let client = reqwest::Client::new();
 
         loop {
@@ -170,21 +402,7 @@ impl ListRecords {
                 endpoint.query_pairs_mut().append_pair("offset", &off);
             }
 
-            let res = client
-                .get(endpoint.to_string())
-                .header("Authorization", format!("Bearer {}", key))
-                .send()
-                .await?;
-
-            let status = res.status();
-            if !status.is_success() {
-                return Err(ApiError::Api {
-                    status,
-                    message: res.text().await?,
-                });
-            }
-
-            let content: ListResponse<T> = res.json().await?;
+            let content: ListResponse<T> = Self::fetch(&client, &self.table, endpoint, key).await?;
             records.extend(content.records);
 
             if let Some(off) = content.offset {
@@ -206,15 +424,17 @@ pub async fn get_record<T>(
     id: &RecordId,
 ) -> Result<Record<T>, ApiError>
 where
-    T: DeserializeOwned
+    T: DeserializeOwned,
 {
     let url = format!("{AIRTABLE_API_BASE}/{base}/{table}/{id}");
 
-    let res = reqwest::Client::new()
-        .get(url)
-        .header("Authorization", format!("Bearer {}", key))
-        .send()
-        .await?;
+    let res = send(
+        ApiRequest::GetRecord,
+        Some(table),
+        reqwest::Client::new().get(url),
+        key,
+    )
+    .await?;
 
     let record = res.json().await?;
     Ok(record)
@@ -244,24 +464,18 @@ where
     map.insert("typecast", serde_json::Value::Bool(typecast));
     map.insert("fields", serde_json::to_value(data)?);
 
-    println!("{}", serde_json::ser::to_string_pretty(&map).unwrap());
-
-    let res = client
-        .patch(url)
-        .header("Authorization", format!("Bearer {}", key))
-        .json(&map)
-        .send()
-        .await?;
+    tracing::debug!(%id, fields = %map["fields"], "updating record");
 
-    let status = res.status();
-    if !status.is_success() {
-        return Err(ApiError::Api {
-            status,
-            message: res.text().await?,
-        });
-    }
+    let res = send(
+        ApiRequest::UpdateRecord,
+        Some(table),
+        client.patch(url).json(&map),
+        key,
+    )
+    .await?;
 
     let record: Record<T> = res.json().await?;
+    cache::cache().invalidate_table(base, table);
 
     Ok(record)
 }
@@ -275,6 +489,15 @@ pub struct Record<T> {
 }
 
 impl<T> Record<T> {
+    /// Builds a record from data stored elsewhere, such as a local copy of the table.
+    pub fn new(id: RecordId, created_time: DateTime<Utc>, fields: T) -> Record<T> {
+        Record {
+            id,
+            created_time,
+            fields,
+        }
+    }
+
     pub fn id(&self) -> &RecordId {
         &self.id
     }
@@ -308,7 +531,93 @@ pub enum ApiError {
     Api { status: StatusCode, message: String },
 }
 
+/// Sends a request to the Airtable API, recording [`metrics`].
+///
+/// The request runs in an `airtable` span with its method, table, status and duration.
+pub(crate) async fn send(
+    kind: ApiRequest,
+    table: Option<&str>,
+    request: reqwest::RequestBuilder,
+    key: &str,
+) -> Result<reqwest::Response, ApiError> {
+    let (client, request) = request
+        .header("Authorization", format!("Bearer {}", key))
+        .build_split();
+    let request = request?;
+
+    // recorded inside whatever span the caller is in, like the request that needed Airtable
+    let span = tracing::info_span!(
+        "airtable",
+        request = kind.label(),
+        method = %request.method(),
+        table,
+        status = tracing::field::Empty,
+        duration_ms = tracing::field::Empty,
+    );
+
+    async move {
+        let started = Instant::now();
+        let res = send_once(kind, &client, request).await;
+
+        let span = tracing::Span::current();
+        span.record("duration_ms", started.elapsed().as_secs_f64() * 1000.0);
+        match &res {
+            Ok(res) => {
+                span.record("status", res.status().as_u16());
+                tracing::debug!("airtable request succeeded");
+            }
+            // callers decide whether an error is worth more than a debug log, e.g. a missing record
+            Err(err) => {
+                if let ApiError::Api { status, .. } = err {
+                    span.record("status", status.as_u16());
+                }
+                tracing::debug!("airtable request failed: {err}");
+            }
+        }
+
+        res
+    }
+    .instrument(span)
+    .await
+}
+
+async fn send_once(
+    kind: ApiRequest,
+    client: &reqwest::Client,
+    request: reqwest::Request,
+) -> Result<reqwest::Response, ApiError> {
+    let started = Instant::now();
+    let res = match client.execute(request).await {
+        Ok(res) => res,
+        Err(err) => {
+            metrics::observe(kind, None, started.elapsed());
+            metrics::error(kind);
+            return Err(err.into());
+        }
+    };
+
+    let status = res.status();
+    metrics::observe(kind, Some(status), started.elapsed());
+
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        metrics::rate_limited(kind);
+    }
+
+    if !status.is_success() {
+        metrics::error(kind);
+        return Err(ApiError::Api {
+            status,
+            message: res.text().await?,
+        });
+    }
+
+    Ok(res)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ApiRequest {
+    /// List records
+    ListRecords,
     /// Get record
     GetRecord,
     /// Update multiple records
@@ -335,6 +644,43 @@ pub enum ApiRequest {
     CreateComment,
     /// Delete comment
     DeleteComment,
+    /// Create webhook
+    CreateWebhook,
+    /// List webhooks
+    ListWebhooks,
+    /// Refresh webhook
+    RefreshWebhook,
+    /// Delete webhook
+    DeleteWebhook,
+    /// List webhook payloads
+    ListPayloads,
+}
+
+impl ApiRequest {
+    /// Name of the request in metrics.
+    pub fn label(&self) -> &'static str {
+        match self {
+            ApiRequest::ListRecords => "list_records",
+            ApiRequest::GetRecord => "get_record",
+            ApiRequest::UpdateRecords => "update_records",
+            ApiRequest::UpdateRecord => "update_record",
+            ApiRequest::CreateRecord => "create_record",
+            ApiRequest::DeleteRecords => "delete_records",
+            ApiRequest::DeleteRecord => "delete_record",
+            ApiRequest::Attachment => "attachment",
+            ApiRequest::UpdateField => "update_field",
+            ApiRequest::CreateField => "create_field",
+            ApiRequest::ListComments => "list_comments",
+            ApiRequest::UpdateComment => "update_comment",
+            ApiRequest::CreateComment => "create_comment",
+            ApiRequest::DeleteComment => "delete_comment",
+            ApiRequest::CreateWebhook => "create_webhook",
+            ApiRequest::ListWebhooks => "list_webhooks",
+            ApiRequest::RefreshWebhook => "refresh_webhook",
+            ApiRequest::DeleteWebhook => "delete_webhook",
+            ApiRequest::ListPayloads => "list_payloads",
+        }
+    }
 }
 
 struct Sort {
@@ -342,7 +688,48 @@ struct Sort {
     direction: Direction,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
 pub enum Direction {
+    #[serde(rename = "asc")]
     Ascending,
+    #[serde(rename = "desc")]
     Descending,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_record_ids() {
+        for (id, valid) in [
+            ("recAbCdEfGh012345", true),
+            ("rec00000000000000", true),
+            ("recABCDEFGHIJKLMN", true),
+            ("", false),
+            ("rec", false),
+            ("recAbCdEfGh01234", false),
+            ("recAbCdEfGh0123456", false),
+            ("RECAbCdEfGh012345", false),
+            ("tblAbCdEfGh012345", false),
+            ("recAbCdEfGh01234-", false),
+            ("recAbCdEfGh01234/", false),
+            ("recAbCdEfGh0123é", false),
+            (" recAbCdEfGh012345", false),
+            ("recAbCdEfGh012345/../tbl", false),
+        ] {
+            assert_eq!(id.parse::<RecordId>().is_ok(), valid, "{id:?}");
+        }
+    }
+
+    #[test]
+    fn deserializes_only_valid_record_ids() {
+        let id: RecordId = serde_json::from_str(r#""recAbCdEfGh012345""#).unwrap();
+        assert_eq!(id.to_string(), "recAbCdEfGh012345");
+
+        let err = serde_json::from_str::<RecordId>(r#""recAbC""#).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("`recAbC` is not a valid record ID"));
+    }
+}
@@ -0,0 +1,306 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        LazyLock, Mutex, MutexGuard,
+    },
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::api::Page;
+
+static CACHE: LazyLock<Cache> = LazyLock::new(Cache::new);
+
+/// The cache shared by every [`ListRecords`](super::api::ListRecords) request
+/// made with [`ListRecords::cache`](super::api::ListRecords::cache).
+///
+/// It starts out disabled, call [`Cache::set_ttl`] to turn it on.
+pub fn cache() -> &'static Cache {
+    &CACHE
+}
+
+/// Everything that goes into a `ListRecords` request,
+/// so requests that could return different records never share an entry.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct CacheKey {
+    pub base: String,
+    pub table: String,
+    /// Query string with the view, fields, formula, sort and page size.
+    pub query: String,
+    pub max_records: Option<usize>,
+    pub offset: Option<String>,
+    /// Whether this was a single page from `request_page` rather than every page from `request`.
+    pub single_page: bool,
+}
+
+struct Entry {
+    page: Page<Value>,
+    stored: Instant,
+}
+
+struct Inner {
+    ttl: Duration,
+    entries: HashMap<CacheKey, Entry>,
+    /// How many times each `(base, table)` has been invalidated.
+    generations: HashMap<(String, String), u64>,
+}
+
+/// In-memory cache of `ListRecords` results that expire after a TTL.
+///
+/// Records are kept as raw JSON so requests for different field types can share it.
+/// A successful [`update_record`](super::api::update_record) drops every entry for its table,
+/// and results fetched before the update but stored after it are discarded.
+pub struct Cache {
+    inner: Mutex<Inner>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheStats {
+    pub ttl_secs: u64,
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl Cache {
+    fn new() -> Cache {
+        Cache {
+            inner: Mutex::new(Inner {
+                ttl: Duration::ZERO,
+                entries: HashMap::new(),
+                generations: HashMap::new(),
+            }),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    fn inner(&self) -> MutexGuard<'_, Inner> {
+        self.inner
+            .lock()
+            .expect("cache lock should not be poisoned")
+    }
+
+    /// Sets how long results are kept. A TTL of zero disables the cache.
+    pub fn set_ttl(&self, ttl: Duration) {
+        let mut inner = self.inner();
+        inner.ttl = ttl;
+        if ttl.is_zero() {
+            inner.entries.clear();
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.inner().ttl
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.ttl().is_zero()
+    }
+
+    pub(crate) fn get(&self, key: &CacheKey) -> Option<Page<Value>> {
+        let inner = self.inner();
+        let page = inner
+            .entries
+            .get(key)
+            .filter(|entry| entry.stored.elapsed() < inner.ttl)
+            .map(|entry| entry.page.clone());
+
+        match page {
+            Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+
+        page
+    }
+
+    /// The number of times a table has been invalidated, to read before fetching
+    /// a result and pass to [`Cache::insert`] along with it.
+    pub(crate) fn generation(&self, base: &str, table: &str) -> u64 {
+        self.inner()
+            .generations
+            .get(&(base.to_owned(), table.to_owned()))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Stores a result that was fetched during `generation` of its table,
+    /// unless the table has been invalidated since.
+    pub(crate) fn insert(&self, key: CacheKey, generation: u64, page: Page<Value>) {
+        let mut inner = self.inner();
+        let ttl = inner.ttl;
+        if ttl.is_zero() {
+            return;
+        }
+
+        let current = inner
+            .generations
+            .get(&(key.base.clone(), key.table.clone()))
+            .copied()
+            .unwrap_or(0);
+        if current != generation {
+            return;
+        }
+
+        inner
+            .entries
+            .retain(|_, entry| entry.stored.elapsed() < ttl);
+        inner.entries.insert(
+            key,
+            Entry {
+                page,
+                stored: Instant::now(),
+            },
+        );
+    }
+
+    /// Drops every cached result for a table, including results that are still being fetched.
+    pub fn invalidate_table(&self, base: &str, table: &str) {
+        let mut inner = self.inner();
+        inner
+            .entries
+            .retain(|key, _| key.base != base || key.table != table);
+        *inner
+            .generations
+            .entry((base.to_owned(), table.to_owned()))
+            .or_default() += 1;
+    }
+
+    pub fn clear(&self) {
+        self.inner().entries.clear();
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let inner = self.inner();
+        CacheStats {
+            ttl_secs: inner.ttl.as_secs(),
+            entries: inner.entries.len(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn key(table: &str, query: &str) -> CacheKey {
+        CacheKey {
+            base: "appA".to_owned(),
+            table: table.to_owned(),
+            query: query.to_owned(),
+            max_records: None,
+            offset: None,
+            single_page: false,
+        }
+    }
+
+    fn page(offset: &str) -> Page<Value> {
+        serde_json::from_value(json!({ "records": [], "offset": offset })).unwrap()
+    }
+
+    fn offset(page: Option<Page<Value>>) -> Option<String> {
+        page.and_then(|page| page.offset)
+    }
+
+    fn enabled() -> Cache {
+        let cache = Cache::new();
+        cache.set_ttl(Duration::from_secs(60));
+        cache
+    }
+
+    #[test]
+    fn separates_keys() {
+        let cache = enabled();
+        let submissions = key("Submissions", "view=Grid");
+        cache.insert(submissions.clone(), 0, page("a"));
+
+        assert_eq!(offset(cache.get(&submissions)), Some("a".to_owned()));
+        assert!(cache.get(&key("Submissions", "view=Queue")).is_none());
+        assert!(cache.get(&key("Reviewers", "view=Grid")).is_none());
+        assert!(cache
+            .get(&CacheKey {
+                single_page: true,
+                ..submissions.clone()
+            })
+            .is_none());
+        assert!(cache
+            .get(&CacheKey {
+                offset: Some("itrA/recA".to_owned()),
+                ..submissions
+            })
+            .is_none());
+    }
+
+    #[test]
+    fn expires_after_ttl() {
+        let cache = enabled();
+        cache.insert(key("Submissions", ""), 0, page("a"));
+        cache.set_ttl(Duration::from_nanos(1));
+        std::thread::sleep(Duration::from_millis(1));
+
+        assert!(cache.get(&key("Submissions", "")).is_none());
+    }
+
+    #[test]
+    fn stores_nothing_when_disabled() {
+        let cache = Cache::new();
+        cache.insert(key("Submissions", ""), 0, page("a"));
+
+        assert!(!cache.enabled());
+        assert_eq!(cache.stats().entries, 0);
+    }
+
+    #[test]
+    fn invalidates_one_table() {
+        let cache = enabled();
+        cache.insert(key("Submissions", ""), 0, page("a"));
+        cache.insert(key("Reviewers", ""), 0, page("b"));
+        cache.invalidate_table("appA", "Submissions");
+
+        assert!(cache.get(&key("Submissions", "")).is_none());
+        assert_eq!(
+            offset(cache.get(&key("Reviewers", ""))),
+            Some("b".to_owned())
+        );
+    }
+
+    #[test]
+    fn drops_results_fetched_before_invalidation() {
+        let cache = enabled();
+        let generation = cache.generation("appA", "Submissions");
+
+        // the listing is still in flight when the update lands
+        cache.invalidate_table("appA", "Submissions");
+        cache.insert(key("Submissions", ""), generation, page("stale"));
+        assert!(cache.get(&key("Submissions", "")).is_none());
+
+        let generation = cache.generation("appA", "Submissions");
+        cache.insert(key("Submissions", ""), generation, page("fresh"));
+        assert_eq!(
+            offset(cache.get(&key("Submissions", ""))),
+            Some("fresh".to_owned())
+        );
+    }
+
+    #[test]
+    fn counts_hits_and_misses() {
+        let cache = enabled();
+        cache.get(&key("Submissions", ""));
+        cache.insert(key("Submissions", ""), 0, page("a"));
+        cache.get(&key("Submissions", ""));
+        cache.get(&key("Submissions", ""));
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (2, 1, 1));
+        assert_eq!(stats.ttl_secs, 60);
+    }
+}
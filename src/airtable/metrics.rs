@@ -0,0 +1,64 @@
+use std::{sync::LazyLock, time::Duration};
+
+use prometheus::{register_histogram_vec, register_int_counter_vec, HistogramVec, IntCounterVec};
+use reqwest::StatusCode;
+
+use super::api::ApiRequest;
+
+// these are registered in the default registry, which the server renders at `/metrics`
+
+static REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "airtable_requests_total",
+        "Requests sent to the Airtable API, by response status",
+        &["request", "status"]
+    )
+    .expect("airtable_requests_total should only be registered once")
+});
+
+static DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "airtable_request_duration_seconds",
+        "Time until Airtable responded",
+        &["request"]
+    )
+    .expect("airtable_request_duration_seconds should only be registered once")
+});
+
+static ERRORS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "airtable_request_errors_total",
+        "Airtable calls that failed",
+        &["request"]
+    )
+    .expect("airtable_request_errors_total should only be registered once")
+});
+
+static RATE_LIMITED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "airtable_rate_limited_total",
+        "Requests that Airtable refused with 429 Too Many Requests",
+        &["request"]
+    )
+    .expect("airtable_rate_limited_total should only be registered once")
+});
+
+/// Records a single request. `status` is `None` when no response was received.
+pub(crate) fn observe(request: ApiRequest, status: Option<StatusCode>, elapsed: Duration) {
+    let status = status.map_or("error".to_owned(), |status| status.as_u16().to_string());
+
+    REQUESTS
+        .with_label_values(&[request.label(), &status])
+        .inc();
+    DURATION
+        .with_label_values(&[request.label()])
+        .observe(elapsed.as_secs_f64());
+}
+
+pub(crate) fn error(request: ApiRequest) {
+    ERRORS.with_label_values(&[request.label()]).inc();
+}
+
+pub(crate) fn rate_limited(request: ApiRequest) {
+    RATE_LIMITED.with_label_values(&[request.label()]).inc();
+}
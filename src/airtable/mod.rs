@@ -1,5 +1,8 @@
 pub mod api;
+pub mod cache;
+mod metrics;
 pub mod types;
+pub mod webhooks;
 
 use std::{collections::HashMap, io, str::FromStr};
 
@@ -9,12 +12,21 @@ use url::Url;
 
 const AIRTABLE_URL_BASE: &str = "https://api.airtable.com/v0/";
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct Base {
     key: String,
     destination: Url,
 }
 
+// written out so the API key can't end up in logs
+impl std::fmt::Debug for Base {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Base")
+            .field("destination", &self.destination)
+            .finish_non_exhaustive()
+    }
+}
+
 impl Base {
     pub fn new(key: String, base: &str, table: &str, view: &str) -> Self {
         let mut uri =
@@ -42,7 +54,7 @@ impl Base {
             .send()
             .await?;
 
-        log::debug!("sent request to {}", self.destination.to_string());
+        tracing::debug!("sent request to {}", self.destination);
 
         let status = res.status();
         let body = match res.json().await {
@@ -54,7 +66,7 @@ impl Base {
             return Err(RequestError::Airtable { status, body });
         }
 
-        return Ok(body);
+        Ok(body)
     }
 
     pub async fn read_records<T>(&self) -> Result<Vec<Record<T>>, RequestError>
@@ -92,6 +104,10 @@ pub struct Record<T> {
 }
 
 impl<T> Record<T> {
+    pub fn created_time(&self) -> &str {
+        &self.created_time
+    }
+
     pub fn fields(&self) -> &T {
         &self.fields
     }
@@ -148,6 +164,63 @@ pub struct Attachment {
     thumbnails: Thumbnails,
 }
 
+impl Attachment {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+
+    /// MIME type of the attachment
+    pub fn content_type(&self) -> &str {
+        &self.ty
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn thumbnails(&self) -> &Thumbnails {
+        &self.thumbnails
+    }
+
+    /// Downloads the contents of the attachment.
+    ///
+    /// Attachment URLs expire after a couple hours,
+    /// so this should be called on a recently fetched record.
+    pub async fn download(&self) -> Result<Vec<u8>, api::ApiError> {
+        download(&self.url).await
+    }
+}
+
+async fn download(url: &str) -> Result<Vec<u8>, api::ApiError> {
+    let res = reqwest::get(url).await?;
+
+    let status = res.status();
+    if !status.is_success() {
+        return Err(api::ApiError::Api {
+            status,
+            message: res.text().await?,
+        });
+    }
+
+    Ok(res.bytes().await?.to_vec())
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Thumbnails {
     small: Thumbnail,
@@ -155,9 +228,42 @@ pub struct Thumbnails {
     full: Thumbnail,
 }
 
+impl Thumbnails {
+    pub fn small(&self) -> &Thumbnail {
+        &self.small
+    }
+
+    pub fn large(&self) -> &Thumbnail {
+        &self.large
+    }
+
+    pub fn full(&self) -> &Thumbnail {
+        &self.full
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Thumbnail {
     url: String,
     width: usize,
     height: usize,
 }
+
+impl Thumbnail {
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Downloads the thumbnail, see [`Attachment::download`].
+    pub async fn download(&self) -> Result<Vec<u8>, api::ApiError> {
+        download(&self.url).await
+    }
+}
@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+
+use super::api::{self, ApiError, ApiRequest, RecordId};
+
+const AIRTABLE_API_BASE: &str = "https://api.airtable.com/v0";
+
+/// Header that notifications are signed in, as `hmac-sha256=<hex digest>`.
+pub const MAC_HEADER: &str = "X-Airtable-Content-MAC";
+
+/// Airtable won't return more than this many payloads per request.
+const PAYLOAD_LIMIT: usize = 50;
+
+/// A webhook that was just created.
+///
+/// The MAC secret is only ever returned here, so it has to be stored to verify notifications.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedWebhook {
+    pub id: String,
+    pub mac_secret_base64: String,
+    pub expiration_time: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+    pub id: String,
+    pub notification_url: Option<String>,
+    pub cursor_for_next_payload: u64,
+    pub are_notifications_enabled: bool,
+    pub is_hook_enabled: bool,
+    pub expiration_time: Option<DateTime<Utc>>,
+    pub last_successful_notification_time: Option<DateTime<Utc>>,
+    pub specification: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct WebhookList {
+    webhooks: Vec<Webhook>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Refreshed {
+    expiration_time: Option<DateTime<Utc>>,
+}
+
+/// The body of a notification sent to a webhook's URL.
+///
+/// It only says which webhook has new payloads, the changes themselves are read
+/// with [`list_payloads`] starting from the cursor after the last payload that was handled.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Notification {
+    pub base: IdRef,
+    pub webhook: IdRef,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdRef {
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PayloadList {
+    pub payloads: Vec<Payload>,
+    /// Pass to the next [`list_payloads`] call to continue after these payloads.
+    pub cursor: u64,
+    pub might_have_more: bool,
+}
+
+/// Changes from a single transaction in the base.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Payload {
+    pub timestamp: DateTime<Utc>,
+    pub base_transaction_number: u64,
+    pub action_metadata: Option<ActionMetadata>,
+    #[serde(default)]
+    pub changed_tables_by_id: HashMap<String, TableChanges>,
+}
+
+/// What made a change, e.g. `client` for the Airtable UI,
+/// `publicApi` for API calls or `formSubmission` for forms.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionMetadata {
+    pub source: String,
+    #[serde(default)]
+    pub source_metadata: Value,
+}
+
+/// Records that changed in a table. Cell values are keyed by field ID, so they are left as JSON.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableChanges {
+    #[serde(default)]
+    pub created_records_by_id: HashMap<RecordId, Value>,
+    #[serde(default)]
+    pub changed_records_by_id: HashMap<RecordId, Value>,
+    #[serde(default)]
+    pub destroyed_record_ids: Vec<RecordId>,
+}
+
+async fn send<T>(
+    kind: ApiRequest,
+    request: reqwest::RequestBuilder,
+    key: &str,
+) -> Result<T, ApiError>
+where
+    T: DeserializeOwned,
+{
+    Ok(api::send(kind, None, request, key).await?.json().await?)
+}
+
+/// Creates a [webhook](https://airtable.com/developers/web/api/webhooks-overview)
+/// for record changes in a base, or only in one table if `table_id` is set.
+///
+/// Webhooks expire after seven days unless they are [refreshed](refresh_webhook).
+pub async fn create_webhook(
+    key: &str,
+    base: &str,
+    notification_url: &str,
+    table_id: Option<&str>,
+) -> Result<CreatedWebhook, ApiError> {
+    let mut filters = json!({ "dataTypes": ["tableData"] });
+    if let Some(table_id) = table_id {
+        filters["recordChangeScope"] = Value::String(table_id.to_owned());
+    }
+
+    let body = json!({
+        "notificationUrl": notification_url,
+        "specification": { "options": { "filters": filters } },
+    });
+
+    let request = reqwest::Client::new()
+        .post(format!("{AIRTABLE_API_BASE}/bases/{base}/webhooks"))
+        .json(&body);
+
+    send(ApiRequest::CreateWebhook, request, key).await
+}
+
+pub async fn list_webhooks(key: &str, base: &str) -> Result<Vec<Webhook>, ApiError> {
+    let request = reqwest::Client::new().get(format!("{AIRTABLE_API_BASE}/bases/{base}/webhooks"));
+    let list: WebhookList = send(ApiRequest::ListWebhooks, request, key).await?;
+
+    Ok(list.webhooks)
+}
+
+/// Extends the life of a webhook, returning its new expiration time.
+pub async fn refresh_webhook(
+    key: &str,
+    base: &str,
+    id: &str,
+) -> Result<Option<DateTime<Utc>>, ApiError> {
+    let request = reqwest::Client::new().post(format!(
+        "{AIRTABLE_API_BASE}/bases/{base}/webhooks/{id}/refresh"
+    ));
+    let refreshed: Refreshed = send(ApiRequest::RefreshWebhook, request, key).await?;
+
+    Ok(refreshed.expiration_time)
+}
+
+pub async fn delete_webhook(key: &str, base: &str, id: &str) -> Result<(), ApiError> {
+    let request =
+        reqwest::Client::new().delete(format!("{AIRTABLE_API_BASE}/bases/{base}/webhooks/{id}"));
+    let _: Value = send(ApiRequest::DeleteWebhook, request, key).await?;
+
+    Ok(())
+}
+
+/// Lists the payloads of a webhook after `cursor`, or from the oldest one that is still kept.
+pub async fn list_payloads(
+    key: &str,
+    base: &str,
+    id: &str,
+    cursor: Option<u64>,
+) -> Result<PayloadList, ApiError> {
+    let mut request = reqwest::Client::new()
+        .get(format!(
+            "{AIRTABLE_API_BASE}/bases/{base}/webhooks/{id}/payloads"
+        ))
+        .query(&[("limit", PAYLOAD_LIMIT)]);
+
+    if let Some(cursor) = cursor {
+        request = request.query(&[("cursor", cursor)]);
+    }
+
+    send(ApiRequest::ListPayloads, request, key).await
+}
+
+/// Checks the [`MAC_HEADER`] of a notification against its raw body.
+///
+/// `secret` is the `macSecretBase64` returned when the webhook was created.
+pub fn verify_mac(secret: &str, body: &[u8], header: &str) -> bool {
+    let Ok(secret) = base64::engine::general_purpose::STANDARD.decode(secret) else {
+        return false;
+    };
+
+    let Some(digest) = header
+        .strip_prefix("hmac-sha256=")
+        .and_then(|digest| hex::decode(digest).ok())
+    else {
+        return false;
+    };
+
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(&secret) else {
+        return false;
+    };
+    mac.update(body);
+
+    mac.verify_slice(&digest).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "c2F5LWNoZWVzZS13ZWJob29rLXNlY3JldA==";
+    const BODY: &[u8] =
+        br#"{"base":{"id":"appA"},"webhook":{"id":"achA"},"timestamp":"2026-10-02T14:03:11.000Z"}"#;
+
+    fn header(secret: &str, body: &[u8]) -> String {
+        let secret = base64::engine::general_purpose::STANDARD
+            .decode(secret)
+            .unwrap();
+        let mut mac = Hmac::<Sha256>::new_from_slice(&secret).unwrap();
+        mac.update(body);
+        format!("hmac-sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    #[test]
+    fn accepts_valid_mac() {
+        assert!(verify_mac(SECRET, BODY, &header(SECRET, BODY)));
+    }
+
+    #[test]
+    fn rejects_tampered_body() {
+        let tampered = String::from_utf8_lossy(BODY).replace("achA", "achB");
+        assert!(!verify_mac(
+            SECRET,
+            tampered.as_bytes(),
+            &header(SECRET, BODY)
+        ));
+    }
+
+    #[test]
+    fn rejects_wrong_secret() {
+        let other = "b3RoZXItd2ViaG9vay1zZWNyZXQ=";
+        assert!(!verify_mac(SECRET, BODY, &header(other, BODY)));
+        assert!(!verify_mac("not base64!", BODY, &header(SECRET, BODY)));
+    }
+
+    #[test]
+    fn rejects_malformed_header() {
+        let valid = header(SECRET, BODY);
+        let digest = valid.strip_prefix("hmac-sha256=").unwrap();
+
+        for header in [
+            "",
+            digest,
+            &format!("hmac-sha1={digest}"),
+            "hmac-sha256=not-hex",
+            &valid[..valid.len() - 2],
+        ] {
+            assert!(!verify_mac(SECRET, BODY, header), "{header:?}");
+        }
+    }
+}
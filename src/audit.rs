@@ -0,0 +1,363 @@
+use std::{collections::BTreeMap, future::Future, path::Path, pin::Pin, sync::Mutex};
+
+use actix_web::{dev::Payload, web, FromRequest, HttpRequest};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use saycheese_review::airtable::api::RecordId;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::{config::Config, error::AppError};
+
+/// Header the review page sends with the reviewer's name.
+pub const REVIEWER_HEADER: &str = "X-Reviewer";
+
+/// What caused a change to a submission.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    /// A reviewer accepted or rejected a submission.
+    Review,
+    /// A new record was linked to a rejected one as its resubmission.
+    Resubmission,
+    /// A rejected submission's resubmission window closed.
+    Expire,
+    /// An accepted submission was exported to the gallery.
+    Export,
+    /// The outcome of sending a decision email was recorded.
+    EmailDelivery,
+    /// A review was reverted.
+    Undo,
+    /// A change made through the `/updatetest` debugging endpoint.
+    Test,
+}
+
+#[derive(Debug, Error)]
+#[error("unknown audit action `{0}`")]
+pub struct UnknownAction(String);
+
+impl AuditAction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Review => "review",
+            AuditAction::Resubmission => "resubmission",
+            AuditAction::Expire => "expire",
+            AuditAction::Export => "export",
+            AuditAction::EmailDelivery => "email_delivery",
+            AuditAction::Undo => "undo",
+            AuditAction::Test => "test",
+        }
+    }
+
+    fn parse(action: &str) -> Result<AuditAction, UnknownAction> {
+        match action {
+            "review" => Ok(AuditAction::Review),
+            "resubmission" => Ok(AuditAction::Resubmission),
+            "expire" => Ok(AuditAction::Expire),
+            "export" => Ok(AuditAction::Export),
+            "email_delivery" => Ok(AuditAction::EmailDelivery),
+            "undo" => Ok(AuditAction::Undo),
+            "test" => Ok(AuditAction::Test),
+            other => Err(UnknownAction(other.to_owned())),
+        }
+    }
+}
+
+/// Who made a change.
+#[derive(Debug, Clone, Serialize)]
+pub struct Actor {
+    pub reviewer: String,
+    pub client_ip: Option<String>,
+}
+
+impl Actor {
+    /// Changes made by the server itself, such as background jobs.
+    pub fn system() -> Actor {
+        Actor {
+            reviewer: "system".to_owned(),
+            client_ip: None,
+        }
+    }
+}
+
+/// The reviewer named in [`REVIEWER_HEADER`], if the request has one.
+pub fn reviewer(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(REVIEWER_HEADER)
+        .and_then(|reviewer| reviewer.to_str().ok())
+        .map(str::trim)
+        .filter(|reviewer| !reviewer.is_empty())
+        .map(ToOwned::to_owned)
+}
+
+/// Reads the reviewer from [`REVIEWER_HEADER`] and the client's IP.
+///
+/// Forwarding headers can be set by anyone, so they are only honored on requests
+/// from one of the [`Config::trusted_proxies`].
+impl FromRequest for Actor {
+    type Error = AppError;
+    type Future = Pin<Box<dyn Future<Output = Result<Actor, AppError>>>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let reviewer = reviewer(req).unwrap_or_else(|| "anonymous".to_owned());
+
+        let peer = req.peer_addr().map(|addr| addr.ip());
+        let from_proxy = peer.is_some_and(|peer| {
+            req.app_data::<web::Data<Config>>()
+                .is_some_and(|config| config.trusted_proxies.contains(&peer))
+        });
+        let client_ip = match from_proxy {
+            true => req
+                .connection_info()
+                .realip_remote_addr()
+                .map(ToOwned::to_owned),
+            false => peer.map(|peer| peer.to_string()),
+        };
+
+        Box::pin(async move {
+            Ok(Actor {
+                reviewer,
+                client_ip,
+            })
+        })
+    }
+}
+
+/// The value of a field before and after a change.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub old: Value,
+    pub new: Value,
+}
+
+/// Changed fields by name.
+pub type Changes = BTreeMap<String, FieldChange>;
+
+/// Compares the fields of two records, keeping only the ones that changed.
+///
+/// `old` can be `None` when only the new values are known,
+/// in which case every field of `new` is recorded with a `null` old value.
+pub fn diff<T, U>(old: Option<&T>, new: &U) -> Result<Changes, serde_json::Error>
+where
+    T: Serialize,
+    U: Serialize,
+{
+    let old = match old {
+        Some(old) => into_object(serde_json::to_value(old)?),
+        None => serde_json::Map::new(),
+    };
+    let new = into_object(serde_json::to_value(new)?);
+
+    let changes = new
+        .into_iter()
+        .filter_map(|(field, new)| {
+            let old = old.get(&field).cloned().unwrap_or(Value::Null);
+            (old != new).then_some((field, FieldChange { old, new }))
+        })
+        .collect();
+
+    Ok(changes)
+}
+
+/// The new value of every changed field, so only those are written back to Airtable
+/// and fields changed elsewhere in the meantime are left alone.
+pub fn patch(changes: &Changes) -> serde_json::Map<String, Value> {
+    changes
+        .iter()
+        .map(|(field, change)| (field.clone(), change.new.clone()))
+        .collect()
+}
+
+fn into_object(value: Value) -> serde_json::Map<String, Value> {
+    match value {
+        Value::Object(object) => object,
+        _ => serde_json::Map::new(),
+    }
+}
+
+/// A single change to a submission.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub at: DateTime<Utc>,
+    pub reviewer: String,
+    pub client_ip: Option<String>,
+    pub record_id: RecordId,
+    pub action: AuditAction,
+    pub changes: Changes,
+}
+
+impl AuditEntry {
+    fn from_row(row: &Row) -> rusqlite::Result<AuditEntry> {
+        let changes: String = row.get("changes")?;
+
+        Ok(AuditEntry {
+            id: row.get("id")?,
+            at: row.get("at")?,
+            reviewer: row.get("reviewer")?,
+            client_ip: row.get("client_ip")?,
+            record_id: row.get::<_, String>("record_id")?.parse().map_err(|err| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    0,
+                    rusqlite::types::Type::Text,
+                    Box::new(err),
+                )
+            })?,
+            action: AuditAction::parse(&row.get::<_, String>("action")?).map_err(|err| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    0,
+                    rusqlite::types::Type::Text,
+                    Box::new(err),
+                )
+            })?,
+            changes: serde_json::from_str(&changes).map_err(|err| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    0,
+                    rusqlite::types::Type::Text,
+                    Box::new(err),
+                )
+            })?,
+        })
+    }
+}
+
+/// Append-only log of every change made to submissions through this server.
+///
+/// Triggers reject any `UPDATE` or `DELETE` on the table,
+/// so entries can't be rewritten after the fact.
+pub struct AuditLog {
+    conn: Mutex<Connection>,
+}
+
+impl AuditLog {
+    pub fn open(path: &Path) -> rusqlite::Result<AuditLog> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS audit (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                at TEXT NOT NULL,
+                reviewer TEXT NOT NULL,
+                client_ip TEXT,
+                record_id TEXT NOT NULL,
+                action TEXT NOT NULL,
+                changes TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS audit_record ON audit (record_id, id);
+            CREATE TRIGGER IF NOT EXISTS audit_no_update BEFORE UPDATE ON audit
+            BEGIN
+                SELECT RAISE(ABORT, 'the audit log is append-only');
+            END;
+            CREATE TRIGGER IF NOT EXISTS audit_no_delete BEFORE DELETE ON audit
+            BEGIN
+                SELECT RAISE(ABORT, 'the audit log is append-only');
+            END;",
+        )?;
+
+        Ok(AuditLog {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn
+            .lock()
+            .expect("audit connection should not be poisoned")
+    }
+
+    pub fn record(
+        &self,
+        actor: &Actor,
+        record_id: &RecordId,
+        action: AuditAction,
+        changes: &Changes,
+    ) -> Result<i64, AppError> {
+        let conn = self.conn();
+        conn.execute(
+            "INSERT INTO audit (at, reviewer, client_ip, record_id, action, changes)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                Utc::now(),
+                actor.reviewer,
+                actor.client_ip,
+                record_id.to_string(),
+                action.as_str(),
+                serde_json::to_string(changes)?
+            ],
+        )?;
+
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Returns the most recent change to a record's review fields,
+    /// ignoring the bookkeeping done for email deliveries.
+    pub fn last_change(&self, record_id: &RecordId) -> rusqlite::Result<Option<AuditEntry>> {
+        self.conn()
+            .query_row(
+                "SELECT * FROM audit WHERE record_id = ?1 AND action != 'email_delivery'
+                ORDER BY id DESC LIMIT 1",
+                [record_id.to_string()],
+                AuditEntry::from_row,
+            )
+            .optional()
+    }
+
+    /// Lists entries, newest first, optionally only those for one record.
+    pub fn list(&self, record_id: Option<&RecordId>) -> rusqlite::Result<Vec<AuditEntry>> {
+        let conn = self.conn();
+        let mut stmt = conn
+            .prepare("SELECT * FROM audit WHERE ?1 IS NULL OR record_id = ?1 ORDER BY id DESC")?;
+        let rows = stmt.query_map([record_id.map(ToString::to_string)], AuditEntry::from_row)?;
+        rows.collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn actions_round_trip() {
+        for action in [
+            AuditAction::Review,
+            AuditAction::Resubmission,
+            AuditAction::Expire,
+            AuditAction::Export,
+            AuditAction::EmailDelivery,
+            AuditAction::Undo,
+            AuditAction::Test,
+        ] {
+            assert_eq!(AuditAction::parse(action.as_str()).ok(), Some(action));
+        }
+    }
+
+    #[test]
+    fn unknown_actions_fail_to_load() {
+        let log = AuditLog::open(Path::new(":memory:")).unwrap();
+        log.conn()
+            .execute(
+                "INSERT INTO audit (at, reviewer, record_id, action, changes)
+                VALUES (?1, 'kestrel', 'recAbCdEfGh012345', 'approve', '{}')",
+                [Utc::now()],
+            )
+            .unwrap();
+
+        assert!(AuditAction::parse("approve").is_err());
+        assert!(log.list(None).is_err());
+    }
+
+    #[test]
+    fn reads_reviewer_header() {
+        let reviewer = |value: Option<&str>| {
+            let mut req = actix_web::test::TestRequest::default();
+            if let Some(value) = value {
+                req = req.insert_header((REVIEWER_HEADER, value));
+            }
+            super::reviewer(&req.to_http_request())
+        };
+
+        assert_eq!(reviewer(Some("  Ada ")), Some("Ada".to_owned()));
+        assert_eq!(reviewer(Some("   ")), None);
+        assert_eq!(reviewer(None), None);
+    }
+}
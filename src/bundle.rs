@@ -0,0 +1,112 @@
+use std::{
+    fs,
+    io::{Cursor, Write},
+    path::Path,
+};
+
+use actix_web::web;
+use saycheese_review::airtable::api::Record;
+use zip::{write::SimpleFileOptions, ZipWriter};
+
+use crate::{error::AppError, manifest::Manifest, qr, Submission};
+
+/// Everything the gallery needs for one project.
+pub struct Bundle {
+    pub manifest: Manifest,
+    qr_image: Vec<u8>,
+    demo: Vec<u8>,
+}
+
+impl Bundle {
+    /// Downloads a submission's QR code and screenshot
+    /// and decodes the QR code to fill in the manifest.
+    pub async fn fetch(rec: &Record<Submission>) -> Result<Bundle, AppError> {
+        let submission = rec.fields();
+        let qr_code = submission.qr_code.first().ok_or_else(|| {
+            AppError::BadRequest("submission does not have a QR code attached".to_owned())
+        })?;
+        let screenshot = submission.screenshot.first().ok_or_else(|| {
+            AppError::BadRequest("submission does not have a screenshot attached".to_owned())
+        })?;
+
+        let qr_image = qr_code.download().await?;
+        let demo = screenshot.download().await?;
+
+        let (qr_image, scan) = web::block(move || {
+            let scan = qr::scan(&qr_image);
+            (qr_image, scan)
+        })
+        .await
+        .map_err(std::io::Error::other)?;
+
+        let payload = scan.codes.into_iter().next().ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "unable to read the submission's QR code: {}",
+                scan.errors.join(", ")
+            ))
+        })?;
+
+        let mut manifest = Manifest::from(rec);
+        manifest.qr.ty = payload.kind.into();
+        manifest.qr.url = payload.payload;
+
+        if let Err(errors) = manifest.validate() {
+            let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+            return Err(AppError::BadRequest(format!(
+                "submission can't be added to the gallery: {}",
+                errors.join(", ")
+            )));
+        }
+
+        Ok(Bundle {
+            manifest,
+            qr_image,
+            demo,
+        })
+    }
+
+    fn files(self) -> Result<Vec<(String, Vec<u8>)>, AppError> {
+        Ok(vec![
+            (
+                "proj.json".to_owned(),
+                serde_json::to_vec_pretty(&self.manifest)?,
+            ),
+            (self.manifest.qr.image, self.qr_image),
+            (self.manifest.demo, self.demo),
+        ])
+    }
+
+    /// Packs the bundle into a zip archive.
+    pub async fn into_zip(self) -> Result<Vec<u8>, AppError> {
+        let files = self.files()?;
+
+        // compression is CPU bound, keep it off the async workers
+        let zip = web::block(move || write_zip(files))
+            .await
+            .map_err(std::io::Error::other)??;
+
+        Ok(zip)
+    }
+
+    /// Writes the bundle's files into `dir`, creating it if needed.
+    pub fn write_to(self, dir: &Path) -> Result<(), AppError> {
+        fs::create_dir_all(dir)?;
+
+        for (name, contents) in self.files()? {
+            fs::write(dir.join(name), contents)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn write_zip(files: Vec<(String, Vec<u8>)>) -> Result<Vec<u8>, zip::result::ZipError> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+
+    for (name, contents) in files {
+        zip.start_file(name, SimpleFileOptions::default())?;
+        zip.write_all(&contents)?;
+    }
+
+    Ok(zip.finish()?.into_inner())
+}
@@ -0,0 +1,151 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use saycheese_review::airtable::api::RecordId;
+
+/// How long a reviewer keeps a submission they were handed before others can get it.
+pub const CLAIM_TTL: TimeDelta = TimeDelta::minutes(10);
+
+struct Claim {
+    reviewer: String,
+    at: DateTime<Utc>,
+}
+
+impl Claim {
+    fn expired(&self, now: DateTime<Utc>) -> bool {
+        now - self.at > CLAIM_TTL
+    }
+}
+
+/// Submissions that a reviewer currently has open, so two reviewers aren't handed the same one.
+///
+/// Claims only live in memory. They aren't written to Airtable as `in_review`,
+/// since a reviewer closing their tab would leave the submission out of the queue for good.
+#[derive(Default)]
+pub struct Claims {
+    claims: Mutex<HashMap<RecordId, Claim>>,
+}
+
+impl Claims {
+    fn claims(&self) -> std::sync::MutexGuard<'_, HashMap<RecordId, Claim>> {
+        self.claims
+            .lock()
+            .expect("claims lock should not be poisoned")
+    }
+
+    /// Claims a submission for `reviewer`, releasing the one they had before.
+    ///
+    /// Returns `false` if someone else already holds it.
+    pub fn claim(&self, id: &RecordId, reviewer: &str) -> bool {
+        let now = Utc::now();
+        let mut claims = self.claims();
+
+        if let Some(claim) = claims.get(id) {
+            if claim.reviewer != reviewer && !claim.expired(now) {
+                return false;
+            }
+        }
+
+        claims.retain(|_, claim| claim.reviewer != reviewer && !claim.expired(now));
+        claims.insert(
+            id.clone(),
+            Claim {
+                reviewer: reviewer.to_owned(),
+                at: now,
+            },
+        );
+
+        true
+    }
+
+    pub fn release(&self, id: &RecordId) {
+        self.claims().remove(id);
+    }
+
+    /// Submissions that are claimed by anyone except `reviewer`.
+    pub fn held_by_others(&self, reviewer: &str) -> Vec<RecordId> {
+        let now = Utc::now();
+        self.claims()
+            .iter()
+            .filter(|(_, claim)| claim.reviewer != reviewer && !claim.expired(now))
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(id: &str) -> RecordId {
+        id.parse().unwrap()
+    }
+
+    const A: &str = "recA1b2C3d4E5f6G7";
+    const B: &str = "recH8i9J0k1L2m3N4";
+
+    #[test]
+    fn refuses_claims_held_by_others() {
+        let claims = Claims::default();
+
+        assert!(claims.claim(&id(A), "ada"));
+        assert!(!claims.claim(&id(A), "grace"));
+        assert!(claims.claim(&id(A), "ada"));
+
+        assert_eq!(claims.held_by_others("grace"), [id(A)]);
+        assert!(claims.held_by_others("ada").is_empty());
+    }
+
+    #[test]
+    fn releases_previous_claim() {
+        let claims = Claims::default();
+
+        assert!(claims.claim(&id(A), "ada"));
+        assert!(claims.claim(&id(B), "ada"));
+
+        assert_eq!(claims.held_by_others("grace"), [id(B)]);
+        assert!(claims.claim(&id(A), "grace"));
+    }
+
+    #[test]
+    fn release_frees_the_submission() {
+        let claims = Claims::default();
+
+        assert!(claims.claim(&id(A), "ada"));
+        claims.release(&id(A));
+
+        assert!(claims.held_by_others("grace").is_empty());
+        assert!(claims.claim(&id(A), "grace"));
+    }
+
+    #[test]
+    fn expired_claims_can_be_taken() {
+        let claims = Claims::default();
+        claims.claims().insert(
+            id(A),
+            Claim {
+                reviewer: "ada".to_owned(),
+                at: Utc::now() - CLAIM_TTL - TimeDelta::seconds(1),
+            },
+        );
+
+        assert!(claims.held_by_others("grace").is_empty());
+        assert!(claims.claim(&id(A), "grace"));
+        assert!(!claims.claim(&id(A), "ada"));
+    }
+
+    #[test]
+    fn claims_last_until_ttl() {
+        let claims = Claims::default();
+        claims.claims().insert(
+            id(A),
+            Claim {
+                reviewer: "ada".to_owned(),
+                at: Utc::now() - CLAIM_TTL + TimeDelta::seconds(30),
+            },
+        );
+
+        assert_eq!(claims.held_by_others("grace"), [id(A)]);
+        assert!(!claims.claim(&id(A), "grace"));
+    }
+}
@@ -0,0 +1,273 @@
+use std::path::PathBuf;
+
+use clap::{ArgGroup, Parser, Subcommand};
+use saycheese_review::airtable::api::{Direction, ListRecords, Record, RecordId};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::{
+    audit::{Actor, AuditLog},
+    config::Config,
+    decision,
+    error::AppError,
+    export,
+    mirror::{Mirror, Store},
+    outbox::Outbox,
+    records::{self, RecordQuery, RecordsPage, SortField},
+    site,
+    status::Status,
+    ReviewRecord, Submission, AIRTABLE_API_KEY, AIRTABLE_BASE_ID, FIELDS, SUBMISSION_TABLE,
+    TABLE_VIEW,
+};
+
+/// Reviews Say Cheese submissions, from the browser or from the terminal.
+///
+/// Every command other than `serve` prints its result as JSON, so it can be piped into other tools.
+/// Settings such as `DATABASE_PATH` and `AIRTABLE_MIRROR` are read from the environment, like the server.
+#[derive(Debug, Parser)]
+#[command(version)]
+pub struct Cli {
+    /// Starts the server if no command is given.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Serves the review page and the API on port 8080.
+    Serve,
+    /// Lists submissions, one page at a time unless `--all` is given.
+    List {
+        /// e.g. `new`, `in_review` or `accepted`.
+        #[arg(long, value_parser = parse_serde::<Status>)]
+        status: Option<Status>,
+        #[arg(long)]
+        os: Option<String>,
+        #[arg(long)]
+        architecture: Option<String>,
+        /// Case-insensitive search through the name, author, email and description.
+        #[arg(long)]
+        search: Option<String>,
+        /// One of `name`, `author`, `os`, `architecture` or `status`.
+        #[arg(long, value_parser = parse_serde::<SortField>)]
+        sort: Option<SortField>,
+        /// `asc` or `desc`.
+        #[arg(long, value_parser = parse_serde::<Direction>, default_value = "asc")]
+        order: Direction,
+        #[arg(long)]
+        page_size: Option<usize>,
+        /// The `next` cursor printed with the previous page.
+        #[arg(long, conflicts_with = "all")]
+        cursor: Option<String>,
+        /// Follows every cursor and prints all matching submissions at once.
+        #[arg(long)]
+        all: bool,
+    },
+    /// Shows a submission along with what was found in its QR code.
+    Show { id: RecordId },
+    /// Accepts or rejects a submission, queueing the decision email if email is configured.
+    #[command(group(ArgGroup::new("decision").required(true).args(["accept", "reject"])))]
+    Review {
+        id: RecordId,
+        #[arg(long)]
+        accept: bool,
+        #[arg(long)]
+        reject: bool,
+        /// Included in the decision email.
+        #[arg(long, default_value = "")]
+        message: String,
+        /// Name recorded in the audit log.
+        #[arg(long, env = "REVIEWER")]
+        reviewer: String,
+    },
+    /// Exports every accepted submission that isn't in the gallery yet.
+    Export {
+        /// Defaults to `EXPORT_DIR`.
+        dir: Option<PathBuf>,
+    },
+    /// Renders the static gallery site from an export.
+    Site {
+        /// Defaults to `EXPORT_DIR`.
+        snapshot: Option<PathBuf>,
+        #[arg(default_value = "site")]
+        out: PathBuf,
+    },
+    /// Brings the local mirror up to date with Airtable.
+    Sync,
+    /// Checks that every submission in the view has the fields and values the reviewer expects.
+    CheckSchema,
+}
+
+/// Parses an argument the same way it's parsed from JSON, so names match the API.
+fn parse_serde<T: DeserializeOwned>(arg: &str) -> Result<T, String> {
+    serde_json::from_value(Value::String(arg.to_owned())).map_err(|err| err.to_string())
+}
+
+fn print<T: Serialize>(value: &T) -> Result<(), AppError> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Runs any command other than [`Command::Serve`], without starting the server.
+pub async fn run(command: Command, config: Config) -> Result<(), AppError> {
+    let audit = AuditLog::open(&config.database)?;
+    let mirror = match config.mirror {
+        true => Some(Mirror::open(&config.database)?),
+        false => None,
+    };
+    let store = Store::new(mirror);
+
+    match command {
+        Command::Serve => unreachable!("the server is started by `main`"),
+        Command::List {
+            status,
+            os,
+            architecture,
+            search,
+            sort,
+            order,
+            page_size,
+            cursor,
+            all,
+        } => {
+            let mut query = RecordQuery {
+                status,
+                os,
+                architecture,
+                q: search,
+                sort,
+                order,
+                page_size,
+                cursor,
+            };
+
+            if !all {
+                return print(&records::list(query).await?);
+            }
+
+            let mut records = Vec::new();
+            loop {
+                let page = records::list(query.clone()).await?;
+                records.extend(page.records);
+
+                match page.next {
+                    Some(next) => query.cursor = Some(next),
+                    None => break,
+                }
+            }
+
+            print(&RecordsPage {
+                records,
+                next: None,
+            })
+        }
+        Command::Show { id } => {
+            let rec = store.get(&id).await?;
+            print(&ReviewRecord::inspect(rec).await)
+        }
+        Command::Review {
+            id,
+            accept,
+            reject: _,
+            message,
+            reviewer,
+        } => {
+            let status = match accept {
+                true => Status::Accepted,
+                false => Status::Rejected,
+            };
+            let actor = Actor {
+                reviewer,
+                client_ip: None,
+            };
+            let outbox = Outbox::open(&config.database)?;
+
+            let decision = decision::decide(
+                &id, status, message, &config, &store, &audit, &outbox, &actor,
+            )
+            .await?;
+            print(&ReviewReport {
+                id: decision.record.id().clone(),
+                status: decision.record.fields().status,
+                email_queued: decision.email_queued,
+            })
+        }
+        Command::Export { dir } => {
+            let dir = dir.unwrap_or(config.export_dir);
+            print(&export::export(&dir, &audit, &Actor::system()).await?)
+        }
+        Command::Site { snapshot, out } => {
+            let snapshot = snapshot.unwrap_or(config.export_dir);
+            print(&site::generate(&snapshot, &out)?)
+        }
+        Command::Sync => {
+            let mirror = store.mirror().ok_or_else(|| {
+                AppError::BadRequest(
+                    "the mirror is disabled, set `AIRTABLE_MIRROR=true`".to_owned(),
+                )
+            })?;
+
+            print(&mirror.sync().await?)
+        }
+        Command::CheckSchema => {
+            let report = check_schema().await?;
+            print(&report)?;
+
+            if !report.invalid.is_empty() {
+                std::process::exit(1);
+            }
+
+            Ok(())
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ReviewReport {
+    id: RecordId,
+    status: Status,
+    email_queued: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct SchemaReport {
+    /// How many submissions were checked.
+    records: usize,
+    invalid: Vec<InvalidRecord>,
+}
+
+/// A submission that doesn't deserialize into a [`Submission`].
+#[derive(Debug, Serialize)]
+struct InvalidRecord {
+    id: RecordId,
+    error: String,
+}
+
+/// Reads every submission in the view as plain JSON, then tries to read it as a [`Submission`].
+///
+/// Airtable refuses the request if any of [`FIELDS`] doesn't exist, and a record fails
+/// if a field has the wrong type or a `status` isn't one of [`Status`].
+async fn check_schema() -> Result<SchemaReport, AppError> {
+    let records: Vec<Record<Value>> =
+        ListRecords::new(AIRTABLE_BASE_ID.to_owned(), SUBMISSION_TABLE.to_owned())
+            .with_view(TABLE_VIEW.to_owned())
+            .with_fields(FIELDS.iter().map(ToString::to_string).collect())
+            .request(AIRTABLE_API_KEY)
+            .await?;
+
+    let invalid = records
+        .iter()
+        .filter_map(|rec| {
+            let err = serde_json::from_value::<Submission>(rec.fields().clone()).err()?;
+            Some(InvalidRecord {
+                id: rec.id().clone(),
+                error: err.to_string(),
+            })
+        })
+        .collect();
+
+    Ok(SchemaReport {
+        records: records.len(),
+        invalid,
+    })
+}
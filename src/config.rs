@@ -0,0 +1,211 @@
+use std::{env, net::IpAddr, path::PathBuf, time::Duration};
+
+use thiserror::Error;
+
+const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Server configuration that is read from the environment at startup.
+///
+/// Airtable credentials are baked in at compile time,
+/// everything here is optional and can change between runs.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// SQLite database holding server state such as the email outbox.
+    pub database: PathBuf,
+    /// Directory that accepted projects are exported to for the gallery.
+    pub export_dir: PathBuf,
+    /// Serve reads from a local copy of the submission table kept in `database`.
+    pub mirror: bool,
+    /// How long listings from Airtable are cached in memory. Zero disables the cache.
+    pub cache_ttl: Duration,
+    /// Which logs to write, as a `tracing` filter such as `info` or `saycheese_review=debug`.
+    pub log_filter: String,
+    pub log_format: LogFormat,
+    /// Reverse proxies whose `Forwarded` and `X-Forwarded-For` headers are believed.
+    /// Requests from anywhere else are attributed to the address they came from.
+    pub trusted_proxies: Vec<IpAddr>,
+    /// Outgoing email settings. Decision emails are not sent if this is `None`.
+    pub mail: Option<MailConfig>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum LogFormat {
+    /// Human readable, for running locally.
+    Pretty,
+    /// One JSON object per line, for log collectors.
+    Json,
+}
+
+#[derive(Debug, Clone)]
+pub struct MailConfig {
+    /// `From` header of decision emails, e.g. `Kestrel <kestrel@hackclub.com>`.
+    pub from: String,
+    pub transport: MailTransport,
+}
+
+#[derive(Debug, Clone)]
+pub enum MailTransport {
+    Smtp {
+        host: String,
+        port: Option<u16>,
+        security: SmtpSecurity,
+        credentials: Option<(String, String)>,
+    },
+    /// Write every email to this directory as an `.eml` file instead of sending it.
+    DryRun(PathBuf),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SmtpSecurity {
+    /// Implicit TLS, usually on port 465.
+    Tls,
+    /// Upgrade to TLS with `STARTTLS`, usually on port 587.
+    StartTls,
+    /// Plaintext, only useful for local SMTP catchers.
+    None,
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("`{0}` must be set")]
+    Missing(&'static str),
+    #[error("`{name}` has an invalid value `{value}`")]
+    Invalid { name: &'static str, value: String },
+}
+
+impl Config {
+    /// Reads the configuration from these environment variables:
+    ///
+    /// - `DATABASE_PATH`: the SQLite database, `saycheese.db` by default.
+    /// - `EXPORT_DIR`: where gallery exports are written, `gallery` by default.
+    /// - `AIRTABLE_MIRROR`: `true` to mirror the submission table locally, `false` by default.
+    /// - `AIRTABLE_CACHE_TTL`: seconds to cache listings from Airtable, 30 by default, 0 disables it.
+    /// - `LOG_LEVEL`: a `tracing` filter for what gets logged, `info` by default.
+    /// - `LOG_FORMAT`: `pretty` (default) or `json`.
+    /// - `TRUSTED_PROXIES`: comma separated addresses of reverse proxies in front of the server.
+    /// - `EMAIL_FROM`: enables decision emails when set.
+    /// - `EMAIL_DRY_RUN_DIR`: write `.eml` files here instead of sending over SMTP.
+    /// - `SMTP_HOST`, `SMTP_PORT`, `SMTP_USERNAME`, `SMTP_PASSWORD`:
+    ///   the SMTP relay to send through.
+    /// - `SMTP_SECURITY`: one of `starttls` (default), `tls` or `none`.
+    pub fn from_env() -> Result<Config, ConfigError> {
+        let database = PathBuf::from(var("DATABASE_PATH").unwrap_or("saycheese.db".to_owned()));
+        let export_dir = PathBuf::from(var("EXPORT_DIR").unwrap_or("gallery".to_owned()));
+        let mirror = match var("AIRTABLE_MIRROR").as_deref() {
+            None | Some("false" | "0") => false,
+            Some("true" | "1") => true,
+            Some(other) => {
+                return Err(ConfigError::Invalid {
+                    name: "AIRTABLE_MIRROR",
+                    value: other.to_owned(),
+                })
+            }
+        };
+
+        let cache_ttl = match var("AIRTABLE_CACHE_TTL") {
+            None => DEFAULT_CACHE_TTL,
+            Some(secs) => match secs.parse() {
+                Ok(secs) => Duration::from_secs(secs),
+                Err(_) => {
+                    return Err(ConfigError::Invalid {
+                        name: "AIRTABLE_CACHE_TTL",
+                        value: secs,
+                    })
+                }
+            },
+        };
+
+        let log_filter = var("LOG_LEVEL").unwrap_or("info".to_owned());
+        let log_format = match var("LOG_FORMAT").as_deref() {
+            None | Some("pretty") => LogFormat::Pretty,
+            Some("json") => LogFormat::Json,
+            Some(other) => {
+                return Err(ConfigError::Invalid {
+                    name: "LOG_FORMAT",
+                    value: other.to_owned(),
+                })
+            }
+        };
+
+        let trusted_proxies = match var("TRUSTED_PROXIES") {
+            None => Vec::new(),
+            Some(proxies) => proxies
+                .split(',')
+                .map(|proxy| proxy.trim().parse())
+                .collect::<Result<_, _>>()
+                .map_err(|_| ConfigError::Invalid {
+                    name: "TRUSTED_PROXIES",
+                    value: proxies.clone(),
+                })?,
+        };
+
+        let Some(from) = var("EMAIL_FROM") else {
+            return Ok(Config {
+                database,
+                export_dir,
+                mirror,
+                cache_ttl,
+                log_filter,
+                log_format,
+                trusted_proxies,
+                mail: None,
+            });
+        };
+
+        let transport = if let Some(dir) = var("EMAIL_DRY_RUN_DIR") {
+            MailTransport::DryRun(PathBuf::from(dir))
+        } else {
+            let host = var("SMTP_HOST").ok_or(ConfigError::Missing("SMTP_HOST"))?;
+            let port = var("SMTP_PORT")
+                .map(|port| {
+                    port.parse().map_err(|_| ConfigError::Invalid {
+                        name: "SMTP_PORT",
+                        value: port,
+                    })
+                })
+                .transpose()?;
+
+            let security = match var("SMTP_SECURITY").as_deref() {
+                None | Some("starttls") => SmtpSecurity::StartTls,
+                Some("tls") => SmtpSecurity::Tls,
+                Some("none") => SmtpSecurity::None,
+                Some(other) => {
+                    return Err(ConfigError::Invalid {
+                        name: "SMTP_SECURITY",
+                        value: other.to_owned(),
+                    })
+                }
+            };
+
+            let credentials = match (var("SMTP_USERNAME"), var("SMTP_PASSWORD")) {
+                (Some(username), Some(password)) => Some((username, password)),
+                (None, None) => None,
+                (Some(_), None) => return Err(ConfigError::Missing("SMTP_PASSWORD")),
+                (None, Some(_)) => return Err(ConfigError::Missing("SMTP_USERNAME")),
+            };
+
+            MailTransport::Smtp {
+                host,
+                port,
+                security,
+                credentials,
+            }
+        };
+
+        Ok(Config {
+            database,
+            export_dir,
+            mirror,
+            cache_ttl,
+            log_filter,
+            log_format,
+            trusted_proxies,
+            mail: Some(MailConfig { from, transport }),
+        })
+    }
+}
+
+/// Reads an environment variable, treating an empty value as unset.
+fn var(name: &str) -> Option<String> {
+    env::var(name).ok().filter(|value| !value.is_empty())
+}
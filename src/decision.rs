@@ -0,0 +1,72 @@
+use chrono::Utc;
+use lettre::message::Mailbox;
+use saycheese_review::airtable::api::{Record, RecordId};
+
+use crate::{
+    audit::{self, Actor, AuditAction, AuditLog},
+    config::Config,
+    email,
+    error::AppError,
+    mirror::Store,
+    outbox::Outbox,
+    resubmit,
+    status::Status,
+    Submission,
+};
+
+pub struct Decision {
+    pub record: Record<Submission>,
+    /// Whether a decision email was queued, which only happens when email is configured.
+    pub email_queued: bool,
+}
+
+/// Records a decision on a submission and, if email is configured, queues the decision email.
+#[allow(clippy::too_many_arguments)]
+pub async fn decide(
+    id: &RecordId,
+    status: Status,
+    message: String,
+    config: &Config,
+    store: &Store,
+    audit: &AuditLog,
+    outbox: &Outbox,
+    actor: &Actor,
+) -> Result<Decision, AppError> {
+    let rec = store.fetch(id).await?;
+    let mut data = rec.fields().clone();
+
+    data.status = data.status.transition(status)?;
+    data.resubmit_deadline = match data.status {
+        Status::Rejected => Some(resubmit::deadline_from(Utc::now())),
+        _ => None,
+    };
+    data.email_message = message;
+
+    // rendered before the decision is saved, so a saved decision always has its email queued
+    let email = match (&config.mail, data.status) {
+        (Some(_), Status::Accepted | Status::Rejected) => Some(email::render(&data)?),
+        _ => None,
+    };
+
+    let changes = audit::diff(Some(rec.fields()), &data)?;
+    let updated = store.update(id, &changes).await?;
+    let audit_id = audit.record(actor, updated.id(), AuditAction::Review, &changes)?;
+
+    if let Some(email) = &email {
+        outbox.enqueue(updated.id(), audit_id, &recipient(&data), email)?;
+    }
+
+    Ok(Decision {
+        record: updated,
+        email_queued: email.is_some(),
+    })
+}
+
+/// Who the decision email goes to. An address that doesn't parse is kept as it is,
+/// so the outbox marks the message failed rather than the decision failing.
+fn recipient(data: &Submission) -> String {
+    match data.email.trim().parse() {
+        Ok(address) => Mailbox::new(Some(data.gallery_attribution.clone()), address).to_string(),
+        Err(_) => data.email.trim().to_owned(),
+    }
+}
@@ -0,0 +1,64 @@
+use std::sync::LazyLock;
+
+use serde::Serialize;
+use tera::{Context, Tera};
+
+use crate::{error::AppError, status::Status, Submission};
+
+const EMAIL_HTML: &str = include_str!("../static/email.html");
+const EMAIL_TEXT: &str = include_str!("../static/email.txt");
+
+static TEMPLATES: LazyLock<Tera> = LazyLock::new(|| {
+    let mut tera = Tera::default();
+    tera.add_raw_templates([("email.html", EMAIL_HTML), ("email.txt", EMAIL_TEXT)])
+        .expect("email templates should be valid");
+    tera
+});
+
+/// A rendered decision email, with both an HTML and a plain-text body.
+#[derive(Debug, Clone, Serialize)]
+pub struct Email {
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+}
+
+/// Renders the decision email for a submission that has been accepted or rejected.
+///
+/// `submission.status` decides which email is rendered,
+/// and `submission.email_message` is included as the reviewer's message.
+pub fn render(submission: &Submission) -> Result<Email, AppError> {
+    let accepted = match submission.status {
+        Status::Accepted => true,
+        Status::Rejected => false,
+        status => {
+            return Err(AppError::BadRequest(format!(
+                "cannot write a decision email for a submission that is `{status}`"
+            )))
+        }
+    };
+
+    let window = match submission.resubmit_deadline {
+        Some(deadline) => format!("until {}", deadline.format("%B %-d at %H:%M UTC")),
+        None => "three days from when you receive this email".to_owned(),
+    };
+
+    let mut context = Context::new();
+    context.insert("name", &submission.gallery_attribution);
+    context.insert("project", &submission.name);
+    context.insert("message", &submission.email_message);
+    context.insert("accepted", &accepted);
+    context.insert("window", &window);
+
+    let subject = if accepted {
+        format!("Say Cheese: {} has been accepted!", submission.name)
+    } else {
+        format!("Say Cheese: an update on {}", submission.name)
+    };
+
+    Ok(Email {
+        subject,
+        html: TEMPLATES.render("email.html", &context)?,
+        text: TEMPLATES.render("email.txt", &context)?,
+    })
+}
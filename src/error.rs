@@ -0,0 +1,172 @@
+use std::io;
+
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use saycheese_review::airtable::{api::ApiError, RequestError};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{mailer::MailError, status::InvalidTransition};
+
+/// Errors that can be returned from a route handler.
+///
+/// Every variant is rendered as a JSON body of the form
+/// `{"status": 502, "message": "...", "details": "..."}`.
+/// Airtable errors leave out `details`, since Airtable's response is only logged.
+#[derive(Debug, Error)]
+pub enum AppError {
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error("Airtable request failed")]
+    Airtable(#[from] ApiError),
+    #[error("Airtable request failed")]
+    LegacyAirtable(RequestError),
+    #[error("unable to encode or decode JSON data")]
+    Json(#[from] serde_json::Error),
+    #[error("unable to render template")]
+    Template(#[from] tera::Error),
+    #[error("the submission was updated, but the email could not be sent")]
+    Mail(#[from] MailError),
+    #[error("unable to access the database")]
+    Database(#[from] rusqlite::Error),
+    #[error("unable to access the filesystem")]
+    Io(#[from] io::Error),
+    #[error("unable to write zip archive")]
+    Zip(#[from] zip::result::ZipError),
+}
+
+impl From<InvalidTransition> for AppError {
+    fn from(value: InvalidTransition) -> Self {
+        AppError::Conflict(value.to_string())
+    }
+}
+
+impl From<RequestError> for AppError {
+    fn from(value: RequestError) -> Self {
+        AppError::LegacyAirtable(value)
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    status: u16,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<String>,
+}
+
+impl AppError {
+    /// Whether the error came from Airtable, whose responses can mention the base and its fields.
+    fn is_airtable(&self) -> bool {
+        matches!(self, AppError::Airtable(_) | AppError::LegacyAirtable(_))
+    }
+
+    fn details(&self) -> Option<String> {
+        match self {
+            AppError::NotFound(_)
+            | AppError::BadRequest(_)
+            | AppError::Conflict(_)
+            | AppError::Unauthorized(_) => None,
+            AppError::Airtable(ApiError::Api { status, message }) => {
+                Some(format!("Airtable responded with {status}: {message}"))
+            }
+            AppError::Airtable(ApiError::Http(err)) => Some(err.to_string()),
+            AppError::Airtable(ApiError::Json(err)) => Some(err.to_string()),
+            AppError::Airtable(ApiError::Url(err)) => Some(err.to_string()),
+            AppError::LegacyAirtable(err) => Some(format!("{err:?}")),
+            AppError::Json(err) => Some(err.to_string()),
+            AppError::Database(err) => Some(err.to_string()),
+            AppError::Template(err) => Some(format!("{err:?}")),
+            AppError::Mail(err) => Some(format!("{err}: {err:?}")),
+            AppError::Io(err) => Some(err.to_string()),
+            AppError::Zip(err) => Some(err.to_string()),
+        }
+    }
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Airtable(err) => match err {
+                ApiError::Api { status, .. } => match status.as_u16() {
+                    404 => StatusCode::NOT_FOUND,
+                    429 => StatusCode::SERVICE_UNAVAILABLE,
+                    _ => StatusCode::BAD_GATEWAY,
+                },
+                ApiError::Http(err) if err.is_timeout() => StatusCode::GATEWAY_TIMEOUT,
+                ApiError::Http(_) | ApiError::Json(_) => StatusCode::BAD_GATEWAY,
+                ApiError::Url(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            AppError::LegacyAirtable(_) | AppError::Mail(_) => StatusCode::BAD_GATEWAY,
+            AppError::Json(_)
+            | AppError::Template(_)
+            | AppError::Database(_)
+            | AppError::Io(_)
+            | AppError::Zip(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+
+        if status.is_server_error() {
+            tracing::error!("{self}: {:?}", self.details());
+        } else if self.is_airtable() {
+            tracing::warn!("{self}: {:?}", self.details());
+        }
+
+        let message = match self {
+            AppError::Airtable(ApiError::Api { status, .. }) if status.as_u16() == 404 => {
+                "record not found".to_owned()
+            }
+            _ => self.to_string(),
+        };
+
+        HttpResponse::build(status).json(ErrorBody {
+            status: status.as_u16(),
+            message,
+            details: self.details().filter(|_| !self.is_airtable()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::body::MessageBody;
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn body(err: AppError) -> Value {
+        let body = err.error_response().into_body().try_into_bytes().unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[test]
+    fn hides_airtable_responses() {
+        let err = AppError::Airtable(ApiError::Api {
+            status: reqwest::StatusCode::UNPROCESSABLE_ENTITY,
+            message: "Unknown field name: \"reviewer notes\" in table tblSubmissions".to_owned(),
+        });
+
+        assert_eq!(
+            body(err),
+            json!({ "status": 502, "message": "Airtable request failed" })
+        );
+    }
+
+    #[test]
+    fn keeps_other_details() {
+        let err = AppError::Database(rusqlite::Error::QueryReturnedNoRows);
+        assert_eq!(body(err)["details"], json!("Query returned no rows"));
+    }
+}
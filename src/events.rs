@@ -0,0 +1,108 @@
+use std::{convert::Infallible, time::Duration};
+
+use actix_web::web::{self, Bytes};
+use futures_util::{stream, Stream, StreamExt};
+use saycheese_review::airtable::api::RecordId;
+use serde::Serialize;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::{mirror::Store, status::Status};
+
+/// Events that haven't been sent to a slow client yet are dropped past this many.
+const CAPACITY: usize = 64;
+
+/// A comment is sent this often when nothing else happens, so proxies don't close the stream.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Something that changed the review queue, sent to every open review page.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// A reviewer was handed a submission by `/nextrecord`.
+    SubmissionClaimed { id: RecordId, reviewer: String },
+    /// A reviewer accepted or rejected a submission, so it left the queue.
+    SubmissionReviewed {
+        id: RecordId,
+        reviewer: String,
+        status: Status,
+    },
+    /// A record was created in Airtable, as reported by a webhook.
+    NewSubmission { id: RecordId },
+    /// How many submissions are waiting for a review.
+    QueueCount { count: usize },
+}
+
+impl Event {
+    fn name(&self) -> &'static str {
+        match self {
+            Event::SubmissionClaimed { .. } => "submission_claimed",
+            Event::SubmissionReviewed { .. } => "submission_reviewed",
+            Event::NewSubmission { .. } => "new_submission",
+            Event::QueueCount { .. } => "queue_count",
+        }
+    }
+
+    /// Formats the event as a server-sent event.
+    fn to_sse(&self) -> Bytes {
+        let data = serde_json::to_string(self).expect("events should serialize to JSON");
+        Bytes::from(format!("event: {}\ndata: {data}\n\n", self.name()))
+    }
+}
+
+/// Broadcasts [`Event`]s to the clients connected to `/events`.
+pub struct Events {
+    sender: broadcast::Sender<Event>,
+}
+
+impl Default for Events {
+    fn default() -> Self {
+        Events {
+            sender: broadcast::channel(CAPACITY).0,
+        }
+    }
+}
+
+impl Events {
+    pub fn publish(&self, event: Event) {
+        // an error only means that nobody is listening
+        let _ = self.sender.send(event);
+    }
+
+    /// Publishes the current length of the queue.
+    pub async fn publish_queue_count(&self, store: &Store) {
+        match store.queue_count().await {
+            Ok(count) => self.publish(Event::QueueCount { count }),
+            Err(err) => tracing::error!("failed to count the queue: {err:?}"),
+        }
+    }
+
+    /// A stream of server-sent events that starts with `initial`, then follows every published event.
+    pub fn stream(&self, initial: Vec<Event>) -> impl Stream<Item = Result<Bytes, Infallible>> {
+        let initial = stream::iter(initial).map(|event| Ok(event.to_sse()));
+
+        let live = stream::unfold(self.sender.subscribe(), |mut receiver| async move {
+            loop {
+                let bytes = match actix_web::rt::time::timeout(KEEPALIVE_INTERVAL, receiver.recv())
+                    .await
+                {
+                    Err(_) => Bytes::from_static(b": keepalive\n\n"),
+                    Ok(Ok(event)) => event.to_sse(),
+                    Ok(Err(RecvError::Lagged(skipped))) => {
+                        tracing::warn!("an event stream fell behind and skipped {skipped} events");
+                        continue;
+                    }
+                    Ok(Err(RecvError::Closed)) => return None,
+                };
+
+                return Some((Ok(bytes), receiver));
+            }
+        });
+
+        initial.chain(live)
+    }
+}
+
+/// Publishes the queue length in the background, so the response that changed it isn't held up.
+pub fn spawn_queue_count(events: web::Data<Events>, store: web::Data<Store>) {
+    actix_web::rt::spawn(async move { events.publish_queue_count(&store).await });
+}
@@ -0,0 +1,187 @@
+use std::{fs, io, path::Path};
+
+use chrono::{DateTime, Utc};
+use saycheese_review::airtable::api::{self, ListRecords, Record, RecordId};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    audit::{self, Actor, AuditAction, AuditLog},
+    bundle::Bundle,
+    error::AppError,
+    status::Status,
+    Submission, AIRTABLE_API_KEY, AIRTABLE_BASE_ID, FIELDS, SUBMISSION_TABLE, TABLE_VIEW,
+};
+
+/// Name of the file listing every exported project.
+pub const INDEX_FILE: &str = "index.json";
+
+/// An entry in `index.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub id: RecordId,
+    /// Directory holding the project's bundle, relative to `index.json`.
+    pub path: String,
+    pub name: String,
+    pub author: String,
+    pub os: String,
+    pub arch: String,
+    /// Small copy of the screenshot in the project's directory, if one could be downloaded.
+    #[serde(default)]
+    pub thumbnail: Option<String>,
+    pub exported_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportFailure {
+    pub id: RecordId,
+    pub error: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ExportReport {
+    pub exported: Vec<IndexEntry>,
+    /// Submissions that couldn't be exported. They stay unmarked and are retried next time.
+    pub failed: Vec<ExportFailure>,
+}
+
+/// Marks a submission as exported, written separately so
+/// no other fields of the [`Submission`] are touched.
+#[derive(Debug, Serialize, Deserialize)]
+struct Exported {
+    exported_at: DateTime<Utc>,
+}
+
+/// Lists accepted submissions that haven't been exported to the gallery yet.
+pub async fn pending() -> Result<Vec<Record<Submission>>, AppError> {
+    let records = ListRecords::new(AIRTABLE_BASE_ID.to_owned(), SUBMISSION_TABLE.to_owned())
+        .with_view(TABLE_VIEW.to_owned())
+        .with_fields(FIELDS.iter().map(ToString::to_string).collect())
+        .with_filter_by_formula(format!(
+            "AND(status = \"{}\", NOT(exported_at))",
+            Status::Accepted
+        ))
+        .request(AIRTABLE_API_KEY)
+        .await?;
+
+    Ok(records)
+}
+
+/// Exports every pending submission into `dir`.
+///
+/// Each project is written to `dir/<record id>/` in the same layout as its bundle zip,
+/// and `dir/index.json` is updated to list every project exported so far.
+/// Exported records have `exported_at` set in Airtable so they are skipped next time.
+pub async fn export(dir: &Path, audit: &AuditLog, actor: &Actor) -> Result<ExportReport, AppError> {
+    fs::create_dir_all(dir)?;
+
+    let mut index = read_index(dir)?;
+    let mut report = ExportReport::default();
+
+    for rec in pending().await? {
+        match export_one(dir, &rec, audit, actor).await {
+            Ok(entry) => {
+                tracing::info!("exported {} to {}", rec.id(), entry.path);
+
+                index.retain(|existing| existing.id != entry.id);
+                index.push(entry.clone());
+                report.exported.push(entry);
+            }
+            Err(err) => {
+                tracing::warn!("unable to export {}: {err:?}", rec.id());
+                report.failed.push(ExportFailure {
+                    id: rec.id().clone(),
+                    error: err.to_string(),
+                });
+            }
+        }
+
+        // written after every project so a failure partway
+        // through doesn't leave bundles missing from the index
+        write_index(dir, &index)?;
+    }
+
+    Ok(report)
+}
+
+async fn export_one(
+    dir: &Path,
+    rec: &Record<Submission>,
+    audit: &AuditLog,
+    actor: &Actor,
+) -> Result<IndexEntry, AppError> {
+    let bundle = Bundle::fetch(rec).await?;
+    let path = rec.id().to_string();
+    let now = Utc::now();
+    let thumbnail = fetch_thumbnail(rec.fields()).await;
+
+    let entry = IndexEntry {
+        id: rec.id().clone(),
+        path: path.clone(),
+        name: bundle.manifest.name.clone(),
+        author: bundle.manifest.author.clone(),
+        os: bundle.manifest.os.clone(),
+        arch: bundle.manifest.arch.clone(),
+        thumbnail: thumbnail.as_ref().map(|(name, _)| name.clone()),
+        exported_at: now,
+    };
+
+    let project_dir = dir.join(path);
+    bundle.write_to(&project_dir)?;
+    if let Some((name, contents)) = thumbnail {
+        fs::write(project_dir.join(name), contents)?;
+    }
+
+    let exported = Exported { exported_at: now };
+    let changes = audit::diff::<Exported, _>(None, &exported)?;
+    api::update_record(
+        AIRTABLE_API_KEY,
+        AIRTABLE_BASE_ID,
+        SUBMISSION_TABLE,
+        rec.id(),
+        exported,
+        false,
+    )
+    .await?;
+    audit.record(actor, rec.id(), AuditAction::Export, &changes)?;
+
+    Ok(entry)
+}
+
+/// Downloads the large thumbnail of a submission's screenshot for the gallery site.
+///
+/// Thumbnails are only a nicety, so failing to get one doesn't fail the export.
+async fn fetch_thumbnail(submission: &Submission) -> Option<(String, Vec<u8>)> {
+    let screenshot = submission.screenshot.first()?;
+
+    let thumbnail = match screenshot.thumbnails().large().download().await {
+        Ok(thumbnail) => thumbnail,
+        Err(err) => {
+            tracing::warn!(
+                "unable to download thumbnail of {}: {err:?}",
+                screenshot.id()
+            );
+            return None;
+        }
+    };
+
+    let extension = image::guess_format(&thumbnail)
+        .ok()
+        .and_then(|format| format.extensions_str().first().copied())
+        .unwrap_or("png");
+
+    Some((format!("thumb.{extension}"), thumbnail))
+}
+
+/// Reads `index.json` from an export directory, which is empty if nothing has been exported yet.
+pub fn read_index(dir: &Path) -> Result<Vec<IndexEntry>, AppError> {
+    match fs::read(dir.join(INDEX_FILE)) {
+        Ok(index) => Ok(serde_json::from_slice(&index)?),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn write_index(dir: &Path, index: &[IndexEntry]) -> Result<(), AppError> {
+    fs::write(dir.join(INDEX_FILE), serde_json::to_vec_pretty(index)?)?;
+    Ok(())
+}
@@ -0,0 +1,205 @@
+use std::{
+    future::Future,
+    path::Path,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use futures_util::future;
+use saycheese_review::airtable::api::{ListRecords, Record};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    mailer::Mailer, AIRTABLE_API_KEY, AIRTABLE_BASE_ID, STATIC_DIR, SUBMISSION_TABLE, TABLE_VIEW,
+};
+
+/// A check that takes longer than this counts as failed.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long the result of the Airtable check is reused,
+/// so an orchestrator polling `/readyz` doesn't use up the base's rate limit.
+const AIRTABLE_CHECK_TTL: Duration = Duration::from_secs(15);
+
+static AIRTABLE_CHECK: CachedCheck = CachedCheck::new(AIRTABLE_CHECK_TTL);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Ok,
+    Failed,
+    /// The dependency isn't configured, so there was nothing to check.
+    Skipped,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub duration_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Readiness {
+    /// Whether no check failed.
+    pub ready: bool,
+    pub checks: Vec<Check>,
+}
+
+/// Runs a check with [`CHECK_TIMEOUT`], timing how long it took.
+async fn run<F>(name: &'static str, check: F) -> Check
+where
+    F: Future<Output = Result<(), String>>,
+{
+    let started = std::time::Instant::now();
+    let result = actix_web::rt::time::timeout(CHECK_TIMEOUT, check)
+        .await
+        .unwrap_or_else(|_| Err(format!("timed out after {CHECK_TIMEOUT:?}")));
+
+    Check {
+        name,
+        status: match result {
+            Ok(()) => CheckStatus::Ok,
+            Err(_) => CheckStatus::Failed,
+        },
+        duration_ms: started.elapsed().as_secs_f64() * 1000.0,
+        error: result.err(),
+    }
+}
+
+fn skipped(name: &'static str) -> Check {
+    Check {
+        name,
+        status: CheckStatus::Skipped,
+        duration_ms: 0.0,
+        error: None,
+    }
+}
+
+/// The last result of a check, reused until it is older than `ttl`.
+struct CachedCheck {
+    ttl: Duration,
+    last: Mutex<Option<(Instant, Result<(), String>)>>,
+}
+
+impl CachedCheck {
+    const fn new(ttl: Duration) -> CachedCheck {
+        CachedCheck {
+            ttl,
+            last: Mutex::new(None),
+        }
+    }
+
+    fn last(&self) -> std::sync::MutexGuard<'_, Option<(Instant, Result<(), String>)>> {
+        self.last
+            .lock()
+            .expect("cached check lock should not be poisoned")
+    }
+
+    async fn get<F>(&self, check: impl FnOnce() -> F) -> Result<(), String>
+    where
+        F: Future<Output = Result<(), String>>,
+    {
+        if let Some((at, result)) = self.last().as_ref() {
+            if at.elapsed() < self.ttl {
+                return result.clone();
+            }
+        }
+
+        let result = check().await;
+        *self.last() = Some((Instant::now(), result.clone()));
+        result
+    }
+}
+
+/// Lists a single record from the submission view, which fails if the base, table or view is gone.
+///
+/// The result is cached for [`AIRTABLE_CHECK_TTL`]. `/readyz` isn't authenticated,
+/// so what Airtable responded with is only logged.
+async fn airtable() -> Result<(), String> {
+    AIRTABLE_CHECK
+        .get(|| async {
+            let _: Vec<Record<Value>> =
+                ListRecords::new(AIRTABLE_BASE_ID.to_owned(), SUBMISSION_TABLE.to_owned())
+                    .with_view(TABLE_VIEW.to_owned())
+                    .with_fields(vec!["status".to_owned()])
+                    .with_max_records(1)
+                    .request(AIRTABLE_API_KEY)
+                    .await
+                    .map_err(|err| {
+                        tracing::warn!("Airtable readiness check failed: {err}: {err:?}");
+                        "Airtable request failed".to_owned()
+                    })?;
+
+            Ok(())
+        })
+        .await
+}
+
+async fn smtp(mailer: &Mailer) -> Result<(), String> {
+    match mailer.test_connection().await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err("the SMTP server did not accept the connection".to_owned()),
+        Err(err) => Err(format!("{err}: {err:?}")),
+    }
+}
+
+async fn static_dir() -> Result<(), String> {
+    match Path::new(STATIC_DIR).is_dir() {
+        true => Ok(()),
+        false => Err(format!("`{STATIC_DIR}` is not a directory")),
+    }
+}
+
+/// Checks every dependency the server needs to handle requests, concurrently.
+pub async fn readiness(mailer: Option<&Mailer>) -> Readiness {
+    let smtp = async {
+        match mailer.filter(|mailer| mailer.uses_smtp()) {
+            Some(mailer) => run("smtp", smtp(mailer)).await,
+            None => skipped("smtp"),
+        }
+    };
+
+    let (airtable, smtp, static_dir) = future::join3(
+        run("airtable", airtable()),
+        smtp,
+        run("static", static_dir()),
+    )
+    .await;
+
+    let checks = vec![airtable, smtp, static_dir];
+    Readiness {
+        ready: checks
+            .iter()
+            .all(|check| check.status != CheckStatus::Failed),
+        checks,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn reuses_recent_results() {
+        let runs = AtomicUsize::new(0);
+        let check = || async {
+            runs.fetch_add(1, Ordering::SeqCst);
+            Err("down".to_owned())
+        };
+
+        let cached = CachedCheck::new(Duration::from_secs(60));
+        assert_eq!(cached.get(check).await, Err("down".to_owned()));
+        assert_eq!(cached.get(check).await, Err("down".to_owned()));
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        let expired = CachedCheck::new(Duration::ZERO);
+        expired.get(check).await.unwrap_err();
+        expired.get(check).await.unwrap_err();
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+    }
+}
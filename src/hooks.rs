@@ -0,0 +1,395 @@
+use std::{path::Path, sync::Mutex, time::Duration};
+
+use actix_web::web;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use saycheese_review::airtable::{
+    api::RecordId,
+    cache,
+    webhooks::{self, Payload},
+};
+use serde::Serialize;
+
+use crate::{
+    error::AppError,
+    events::{Event, Events},
+    mirror::{Store, SyncReport},
+    AIRTABLE_API_KEY, AIRTABLE_BASE_ID, SUBMISSION_TABLE,
+};
+
+/// How often registered webhooks are refreshed. Airtable expires them after seven days.
+pub const REFRESH_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A webhook created through this server.
+#[derive(Debug, Clone, Serialize)]
+pub struct RegisteredWebhook {
+    pub id: String,
+    pub notification_url: String,
+    pub expiration_time: Option<DateTime<Utc>>,
+}
+
+/// What a notification changed.
+#[derive(Debug, Default, Serialize)]
+pub struct HookReport {
+    pub payloads: usize,
+    pub created: usize,
+    pub changed: usize,
+    pub destroyed: usize,
+    /// The mirror sync that was run to pick up the changes, if the mirror is enabled.
+    pub sync: Option<SyncReport>,
+}
+
+/// Records touched by a run of payloads, across every table the webhook watches.
+#[derive(Debug, Default)]
+struct Tally {
+    payloads: usize,
+    created: Vec<RecordId>,
+    changed: usize,
+    destroyed: Vec<RecordId>,
+}
+
+impl Tally {
+    fn add(&mut self, payloads: &[Payload]) {
+        for changes in payloads
+            .iter()
+            .flat_map(|payload| payload.changed_tables_by_id.values())
+        {
+            self.created
+                .extend(changes.created_records_by_id.keys().cloned());
+            self.changed += changes.changed_records_by_id.len();
+            self.destroyed
+                .extend(changes.destroyed_record_ids.iter().cloned());
+        }
+
+        self.payloads += payloads.len();
+    }
+}
+
+/// Airtable webhooks that notify `/hooks/airtable`,
+/// along with the secrets to verify them and how far their payloads have been read.
+pub struct Hooks {
+    conn: Mutex<Connection>,
+}
+
+impl Hooks {
+    pub fn open(path: &Path) -> rusqlite::Result<Hooks> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS webhooks (
+                id TEXT PRIMARY KEY,
+                notification_url TEXT NOT NULL,
+                mac_secret TEXT NOT NULL,
+                cursor INTEGER,
+                expiration_time TEXT
+            );",
+        )?;
+
+        Ok(Hooks {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn
+            .lock()
+            .expect("webhook connection should not be poisoned")
+    }
+
+    pub fn list(&self) -> rusqlite::Result<Vec<RegisteredWebhook>> {
+        let conn = self.conn();
+        let mut stmt =
+            conn.prepare("SELECT id, notification_url, expiration_time FROM webhooks ORDER BY id")?;
+        let rows = stmt.query_map([], |row| {
+            Ok(RegisteredWebhook {
+                id: row.get("id")?,
+                notification_url: row.get("notification_url")?,
+                expiration_time: row.get("expiration_time")?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    fn cursor(&self, id: &str) -> rusqlite::Result<Option<u64>> {
+        let cursor: Option<i64> = self
+            .conn()
+            .query_row("SELECT cursor FROM webhooks WHERE id = ?1", [id], |row| {
+                row.get(0)
+            })
+            .optional()?
+            .flatten();
+
+        Ok(cursor.map(|cursor| cursor as u64))
+    }
+
+    fn set_cursor(&self, id: &str, cursor: u64) -> rusqlite::Result<()> {
+        self.conn().execute(
+            "UPDATE webhooks SET cursor = ?2 WHERE id = ?1",
+            params![id, cursor as i64],
+        )?;
+        Ok(())
+    }
+
+    /// Creates a webhook in Airtable that sends notifications to `notification_url`,
+    /// which should be the public address of `/hooks/airtable`.
+    pub async fn create(
+        &self,
+        notification_url: &str,
+        table_id: Option<&str>,
+    ) -> Result<RegisteredWebhook, AppError> {
+        let created = webhooks::create_webhook(
+            AIRTABLE_API_KEY,
+            AIRTABLE_BASE_ID,
+            notification_url,
+            table_id,
+        )
+        .await?;
+
+        self.conn().execute(
+            "INSERT INTO webhooks (id, notification_url, mac_secret, expiration_time)
+            VALUES (?1, ?2, ?3, ?4)",
+            params![
+                created.id,
+                notification_url,
+                created.mac_secret_base64,
+                created.expiration_time
+            ],
+        )?;
+
+        Ok(RegisteredWebhook {
+            id: created.id,
+            notification_url: notification_url.to_owned(),
+            expiration_time: created.expiration_time,
+        })
+    }
+
+    /// Deletes a webhook from Airtable and forgets its secret.
+    pub async fn delete(&self, id: &str) -> Result<(), AppError> {
+        webhooks::delete_webhook(AIRTABLE_API_KEY, AIRTABLE_BASE_ID, id).await?;
+        self.conn()
+            .execute("DELETE FROM webhooks WHERE id = ?1", [id])?;
+        Ok(())
+    }
+
+    /// Checks that a notification for webhook `id` was signed with its secret.
+    pub fn verify(&self, id: &str, body: &[u8], mac: &str) -> Result<(), AppError> {
+        let secret: String = self
+            .conn()
+            .query_row(
+                "SELECT mac_secret FROM webhooks WHERE id = ?1",
+                [id],
+                |row| row.get(0),
+            )
+            .optional()?
+            .ok_or_else(|| AppError::NotFound(format!("webhook {id} is not registered")))?;
+
+        if !webhooks::verify_mac(&secret, body, mac) {
+            return Err(AppError::Unauthorized(format!(
+                "`{}` does not match the notification",
+                webhooks::MAC_HEADER
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Reads every payload that arrived since the last notification,
+    /// then drops cached listings and syncs the mirror if any records changed.
+    ///
+    /// Created records are announced to open review pages as new submissions.
+    /// The cursor is saved after each page, so if a later page fails the changes
+    /// that were already read are still applied and aren't read again.
+    pub async fn handle(
+        &self,
+        id: &str,
+        store: &Store,
+        events: &Events,
+    ) -> Result<HookReport, AppError> {
+        let mut tally = Tally::default();
+        let read = self.read_payloads(id, &mut tally).await;
+
+        let mut report = HookReport {
+            payloads: tally.payloads,
+            created: tally.created.len(),
+            changed: tally.changed,
+            destroyed: tally.destroyed.len(),
+            sync: None,
+        };
+
+        if report.created + report.changed + report.destroyed > 0 {
+            cache::cache().invalidate_table(AIRTABLE_BASE_ID, SUBMISSION_TABLE);
+
+            if let Some(mirror) = store.mirror() {
+                mirror.remove(&tally.destroyed)?;
+                report.sync = Some(mirror.sync().await?);
+            }
+
+            for id in tally.created {
+                events.publish(Event::NewSubmission { id });
+            }
+            events.publish_queue_count(store).await;
+        }
+
+        read?;
+        Ok(report)
+    }
+
+    /// Adds up the payloads after the saved cursor one page at a time, saving the cursor after each.
+    async fn read_payloads(&self, id: &str, tally: &mut Tally) -> Result<(), AppError> {
+        let mut cursor = self.cursor(id)?;
+
+        loop {
+            let list =
+                webhooks::list_payloads(AIRTABLE_API_KEY, AIRTABLE_BASE_ID, id, cursor).await?;
+
+            tally.add(&list.payloads);
+            self.set_cursor(id, list.cursor)?;
+            cursor = Some(list.cursor);
+
+            if !list.might_have_more {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Refreshes every registered webhook so it doesn't expire.
+    pub async fn refresh(&self) -> Result<(), AppError> {
+        for webhook in self.list()? {
+            let expiration_time =
+                webhooks::refresh_webhook(AIRTABLE_API_KEY, AIRTABLE_BASE_ID, &webhook.id).await?;
+
+            self.conn().execute(
+                "UPDATE webhooks SET expiration_time = ?2 WHERE id = ?1",
+                params![webhook.id, expiration_time],
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs [`Hooks::refresh`] every [`REFRESH_INTERVAL`] for as long as the server is up.
+pub async fn refresh_periodically(hooks: web::Data<Hooks>) {
+    let mut interval = actix_web::rt::time::interval(REFRESH_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(err) = hooks.refresh().await {
+            tracing::error!("failed to refresh webhooks: {err:?}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use saycheese_review::airtable::webhooks::PayloadList;
+
+    /// Two pages of payloads as Airtable sent them: a form submission, a reviewer editing
+    /// two records, a deletion, and a payload for a table change that touched no records.
+    const PAGES: [&str; 2] = [
+        r#"{
+            "payloads": [
+                {
+                    "timestamp": "2026-10-02T14:03:11.000Z",
+                    "baseTransactionNumber": 412,
+                    "actionMetadata": { "source": "formSubmission", "sourceMetadata": {} },
+                    "payloadFormat": "v0",
+                    "changedTablesById": {
+                        "tblSubmissions00": {
+                            "createdRecordsById": {
+                                "recA1b2C3d4E5f6G7": {
+                                    "createdTime": "2026-10-02T14:03:11.000Z",
+                                    "cellValuesByFieldId": { "fldName": "Pocket Tetris" }
+                                }
+                            }
+                        }
+                    }
+                },
+                {
+                    "timestamp": "2026-10-02T15:20:45.000Z",
+                    "baseTransactionNumber": 413,
+                    "actionMetadata": { "source": "client", "sourceMetadata": {} },
+                    "payloadFormat": "v0",
+                    "changedTablesById": {
+                        "tblSubmissions00": {
+                            "changedRecordsById": {
+                                "recH8i9J0k1L2m3N4": {
+                                    "current": { "cellValuesByFieldId": { "fldStatus": "in_review" } },
+                                    "previous": { "cellValuesByFieldId": { "fldStatus": "new" } }
+                                },
+                                "recO5p6Q7r8S9t0U1": {
+                                    "current": { "cellValuesByFieldId": { "fldStatus": "accepted" } },
+                                    "previous": { "cellValuesByFieldId": { "fldStatus": "in_review" } }
+                                }
+                            }
+                        }
+                    }
+                }
+            ],
+            "cursor": 414,
+            "mightHaveMore": true,
+            "payloadFormat": "v0"
+        }"#,
+        r#"{
+            "payloads": [
+                {
+                    "timestamp": "2026-10-03T09:00:02.000Z",
+                    "baseTransactionNumber": 414,
+                    "actionMetadata": { "source": "publicApi", "sourceMetadata": {} },
+                    "payloadFormat": "v0",
+                    "changedTablesById": {
+                        "tblSubmissions00": {
+                            "destroyedRecordIds": ["recV2w3X4y5Z6a7B8"]
+                        }
+                    }
+                },
+                {
+                    "timestamp": "2026-10-03T09:12:40.000Z",
+                    "baseTransactionNumber": 415,
+                    "actionMetadata": { "source": "client", "sourceMetadata": {} },
+                    "payloadFormat": "v0"
+                }
+            ],
+            "cursor": 416,
+            "mightHaveMore": false,
+            "payloadFormat": "v0"
+        }"#,
+    ];
+
+    #[test]
+    fn tallies_recorded_payloads() {
+        let mut tally = Tally::default();
+        let mut cursor = None;
+        for page in PAGES {
+            let list: PayloadList = serde_json::from_str(page).unwrap();
+            tally.add(&list.payloads);
+            cursor = Some(list.cursor);
+        }
+
+        let ids =
+            |ids: &[&str]| -> Vec<RecordId> { ids.iter().map(|id| id.parse().unwrap()).collect() };
+        assert_eq!(tally.payloads, 4);
+        assert_eq!(tally.created, ids(&["recA1b2C3d4E5f6G7"]));
+        assert_eq!(tally.changed, 2);
+        assert_eq!(tally.destroyed, ids(&["recV2w3X4y5Z6a7B8"]));
+        assert_eq!(cursor, Some(416));
+    }
+
+    #[test]
+    fn saves_cursor_per_webhook() {
+        let hooks = Hooks::open(Path::new(":memory:")).unwrap();
+        hooks
+            .conn()
+            .execute(
+                "INSERT INTO webhooks (id, notification_url, mac_secret) VALUES ('achA', 'url', 'secret')",
+                [],
+            )
+            .unwrap();
+
+        assert_eq!(hooks.cursor("achA").unwrap(), None);
+        hooks.set_cursor("achA", 414).unwrap();
+        assert_eq!(hooks.cursor("achA").unwrap(), Some(414));
+        assert_eq!(hooks.cursor("achB").unwrap(), None);
+    }
+}
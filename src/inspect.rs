@@ -0,0 +1,857 @@
+use base64::Engine;
+use serde::Serialize;
+
+use crate::{
+    qr::{self, DecodedQr, EccLevel, PayloadKind},
+    Submission,
+};
+
+/// Byte mode capacity of every QR version, indexed by `[version - 1][ecc]`
+/// with error correction levels in `L, M, Q, H` order.
+const BYTE_CAPACITY: [[usize; 4]; 40] = [
+    [17, 14, 11, 7],
+    [32, 26, 20, 14],
+    [53, 42, 32, 24],
+    [78, 62, 46, 34],
+    [106, 84, 60, 44],
+    [134, 106, 74, 58],
+    [154, 122, 86, 64],
+    [192, 152, 108, 84],
+    [230, 180, 130, 98],
+    [271, 213, 151, 119],
+    [321, 251, 177, 137],
+    [367, 287, 203, 155],
+    [425, 331, 241, 177],
+    [458, 362, 258, 194],
+    [520, 412, 292, 220],
+    [586, 450, 322, 250],
+    [644, 504, 364, 280],
+    [718, 560, 394, 310],
+    [792, 624, 442, 338],
+    [858, 666, 482, 382],
+    [929, 711, 509, 403],
+    [1003, 779, 565, 439],
+    [1091, 857, 611, 461],
+    [1171, 911, 661, 511],
+    [1273, 997, 715, 535],
+    [1367, 1059, 751, 593],
+    [1465, 1125, 805, 625],
+    [1528, 1190, 868, 658],
+    [1628, 1264, 908, 698],
+    [1732, 1370, 982, 742],
+    [1840, 1452, 1030, 790],
+    [1952, 1538, 1112, 842],
+    [2068, 1628, 1168, 898],
+    [2188, 1722, 1228, 958],
+    [2303, 1809, 1283, 983],
+    [2431, 1911, 1351, 1051],
+    [2563, 1989, 1423, 1093],
+    [2699, 2099, 1499, 1139],
+    [2809, 2213, 1579, 1219],
+    [2953, 2331, 1663, 1273],
+];
+
+/// Characters allowed in alphanumeric mode.
+const ALPHANUMERIC: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+/// Class files start with the same magic as fat Mach-O binaries, followed by their version,
+/// which is never below this. Fat binaries have their number of architectures there instead.
+const MIN_CLASS_FILE_VERSION: u32 = 45;
+
+/// Versions above this are dense enough that they're hard to scan off a print.
+const DENSE_VERSION: usize = 25;
+
+/// Returns how many bytes fit in a QR code of `version` at `ecc_level`.
+pub fn byte_capacity(version: usize, ecc_level: EccLevel) -> Option<usize> {
+    let level = match ecc_level {
+        EccLevel::L => 0,
+        EccLevel::M => 1,
+        EccLevel::Q => 2,
+        EccLevel::H => 3,
+    };
+
+    BYTE_CAPACITY
+        .get(version.checked_sub(1)?)
+        .map(|capacities| capacities[level])
+}
+
+/// How characters are packed into a QR code, from densest to least dense.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    Numeric,
+    Alphanumeric,
+    Byte,
+}
+
+impl Mode {
+    /// The densest mode that can hold all of `data`.
+    fn of(data: &[u8]) -> Mode {
+        if data.iter().all(u8::is_ascii_digit) {
+            Mode::Numeric
+        } else if data.iter().all(|byte| ALPHANUMERIC.contains(byte)) {
+            Mode::Alphanumeric
+        } else {
+            Mode::Byte
+        }
+    }
+
+    /// Bits used to store the number of characters, which grows with the version.
+    fn count_bits(self, version: usize) -> usize {
+        let group = match version {
+            ..=9 => 0,
+            10..=26 => 1,
+            _ => 2,
+        };
+
+        match self {
+            Mode::Numeric => [10, 12, 14][group],
+            Mode::Alphanumeric => [9, 11, 13][group],
+            Mode::Byte => [8, 16, 16][group],
+        }
+    }
+}
+
+/// Returns how many characters in `mode` fit in a QR code of `version` at `ecc_level`.
+pub fn capacity_in(version: usize, ecc_level: EccLevel, mode: Mode) -> Option<usize> {
+    let bytes = byte_capacity(version, ecc_level)?;
+
+    // byte capacities are the data codewords left after the 4 bit mode indicator
+    // and the character count, rounded down to whole bytes
+    let codewords = bytes + (4 + Mode::Byte.count_bits(version)).div_ceil(8);
+    let bits = codewords * 8 - 4 - mode.count_bits(version);
+
+    Some(match mode {
+        // 10 bits for every 3 digits, then 4 bits for 1 more or 7 bits for 2 more
+        Mode::Numeric => {
+            bits / 10 * 3
+                + match bits % 10 {
+                    7.. => 2,
+                    4.. => 1,
+                    _ => 0,
+                }
+        }
+        // 11 bits for every 2 characters, then 6 bits for 1 more
+        Mode::Alphanumeric => bits / 11 * 2 + usize::from(bits % 11 >= 6),
+        Mode::Byte => bits / 8,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+/// Something a reviewer should know about a QR payload.
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// How a payload fits within QR code size limits.
+#[derive(Debug, Clone, Serialize)]
+pub struct Capacity {
+    /// The densest mode the whole payload could be stored in. Capacities are counted in it.
+    pub mode: Mode,
+    /// Characters that fit in the scanned version at the scanned error correction level.
+    pub version_capacity: Option<usize>,
+    /// Characters that fit in the largest version at the scanned error correction level.
+    pub max_capacity: usize,
+    /// The smallest version the payload fits in at the scanned error correction level.
+    pub min_version: Option<usize>,
+    pub fits: bool,
+}
+
+/// File format of a payload, sniffed from its first few bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    Elf,
+    Pe,
+    MachO,
+    Wasm,
+    Html,
+    Script,
+    Gzip,
+    Zlib,
+    Zip,
+    Xz,
+    Bzip2,
+    Zstd,
+    JavaClass,
+    Text,
+    Unknown,
+}
+
+impl Format {
+    fn is_compressed(self) -> bool {
+        matches!(
+            self,
+            Format::Gzip | Format::Zlib | Format::Zip | Format::Xz | Format::Bzip2 | Format::Zstd
+        )
+    }
+}
+
+/// The platform a payload targets, as far as can be told from its contents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Platform {
+    pub os: Os,
+    pub arch: Option<Arch>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Os {
+    Linux,
+    Windows,
+    Macos,
+    /// Runs in a browser, so it works on every OS.
+    Web,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Arch {
+    X86,
+    X86_64,
+    Arm,
+    Aarch64,
+    RiscV,
+}
+
+impl Os {
+    /// Interprets the free-form `os` field of a submission.
+    fn parse(declared: &str) -> Option<Os> {
+        let declared = declared.to_ascii_lowercase();
+
+        // before `win`, which is part of `darwin`
+        if declared.contains("mac") || declared.contains("darwin") || declared.contains("osx") {
+            Some(Os::Macos)
+        } else if declared.contains("win") {
+            Some(Os::Windows)
+        } else if declared.contains("linux") {
+            Some(Os::Linux)
+        } else if declared.contains("web") || declared.contains("browser") {
+            Some(Os::Web)
+        } else {
+            None
+        }
+    }
+}
+
+impl Arch {
+    /// Interprets the free-form `architecture` field of a submission.
+    fn parse(declared: &str) -> Option<Arch> {
+        let declared = declared.to_ascii_lowercase();
+
+        if declared.contains("x86_64")
+            || declared.contains("x86-64")
+            || declared.contains("amd64")
+            || declared.contains("x64")
+        {
+            Some(Arch::X86_64)
+        } else if declared.contains("x86") || declared.contains("i386") || declared.contains("i686")
+        {
+            Some(Arch::X86)
+        } else if declared.contains("aarch64") || declared.contains("arm64") {
+            Some(Arch::Aarch64)
+        } else if declared.contains("arm") {
+            Some(Arch::Arm)
+        } else if declared.contains("risc") {
+            Some(Arch::RiscV)
+        } else {
+            None
+        }
+    }
+}
+
+/// What was found out about a single QR payload.
+#[derive(Debug, Clone, Serialize)]
+pub struct Inspection {
+    /// MIME type declared by a data URI.
+    pub mime_type: Option<String>,
+    /// Bytes stored in the QR code.
+    pub payload_size: usize,
+    /// Bytes of content after decoding a data URI or base64 text.
+    pub decoded_size: Option<usize>,
+    pub format: Format,
+    pub platform: Option<Platform>,
+    pub capacity: Capacity,
+    pub findings: Vec<Finding>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InspectedQr {
+    #[serde(flatten)]
+    pub code: DecodedQr,
+    pub inspection: Inspection,
+}
+
+/// Every QR code on a submission along with what was found out about each one.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct QrReport {
+    pub codes: Vec<InspectedQr>,
+    pub errors: Vec<String>,
+}
+
+/// Scans and inspects the QR code attached to a submission.
+///
+/// Failing to download or decode the image is reported in [`QrReport::errors`]
+/// rather than returned, so it can always be shown next to the record.
+pub async fn inspect_submission(submission: &Submission) -> QrReport {
+    let Some(attachment) = submission.qr_code.first() else {
+        return QrReport {
+            codes: Vec::new(),
+            errors: vec!["submission does not have a QR code attached".to_owned()],
+        };
+    };
+
+    let scan = match qr::scan_attachment(attachment).await {
+        Ok(scan) => scan,
+        Err(err) => {
+            return QrReport {
+                codes: Vec::new(),
+                errors: vec![format!("unable to scan QR code: {err}")],
+            }
+        }
+    };
+
+    QrReport {
+        codes: scan
+            .codes
+            .into_iter()
+            .map(|code| InspectedQr {
+                inspection: inspect(&code, &submission.os, &submission.architecture),
+                code,
+            })
+            .collect(),
+        errors: scan.errors,
+    }
+}
+
+/// Classifies a decoded payload and checks it against the declared `os` and `architecture`.
+pub fn inspect(code: &DecodedQr, os: &str, arch: &str) -> Inspection {
+    let mut findings = Vec::new();
+
+    let raw = match code.kind {
+        PayloadKind::Binary => base64::prelude::BASE64_STANDARD
+            .decode(&code.payload)
+            .unwrap_or_default(),
+        _ => code.payload.as_bytes().to_vec(),
+    };
+
+    let (mime_type, content) = match code.kind {
+        PayloadKind::DataUri => match parse_data_uri(&code.payload) {
+            Some((mime, data)) => (Some(mime), Some(data)),
+            None => {
+                findings.push(Finding {
+                    severity: Severity::Error,
+                    message: "data URI is malformed and can't be opened".to_owned(),
+                });
+                (None, None)
+            }
+        },
+        PayloadKind::Url => {
+            match url::Url::parse(code.payload.trim()) {
+                Ok(url) if url.scheme() == "http" => findings.push(Finding {
+                    severity: Severity::Warning,
+                    message: "URL uses plain http".to_owned(),
+                }),
+                Ok(_) => {}
+                Err(err) => findings.push(Finding {
+                    severity: Severity::Error,
+                    message: format!("URL is invalid: {err}"),
+                }),
+            }
+            (None, None)
+        }
+        PayloadKind::Text => (None, decode_base64_text(&code.payload)),
+        PayloadKind::Binary => (None, Some(raw.clone())),
+    };
+
+    let sniffed = content.as_deref().unwrap_or(&raw);
+    let (mut format, mut platform) = sniff(sniffed);
+
+    // fragments of HTML don't have a doctype to sniff, so trust the data URI
+    if format == Format::Text && mime_type.as_deref() == Some("text/html") {
+        format = Format::Html;
+        platform = Some(Platform {
+            os: Os::Web,
+            arch: None,
+        });
+    }
+
+    if format.is_compressed() {
+        findings.push(Finding {
+            severity: Severity::Info,
+            message: format!("payload is {format:?} compressed, its contents weren't inspected"),
+        });
+    }
+
+    findings.extend(check_platform(platform, os, arch));
+
+    let capacity = capacity(&raw, code.version, code.ecc_level);
+    if !capacity.fits {
+        // the code was just decoded, so the payload did fit by splitting it into segments
+        findings.push(Finding {
+            severity: Severity::Info,
+            message: format!(
+                "payload is {} characters but a version {} code at level {:?} only holds {} in {:?} mode, so it mixes modes",
+                raw.len(),
+                code.version,
+                code.ecc_level,
+                capacity.version_capacity.unwrap_or(0),
+                capacity.mode,
+            ),
+        });
+    }
+
+    if code.version > DENSE_VERSION {
+        findings.push(Finding {
+            severity: Severity::Warning,
+            message: format!(
+                "version {} codes are very dense and may not scan once printed",
+                code.version
+            ),
+        });
+    }
+
+    Inspection {
+        mime_type,
+        payload_size: raw.len(),
+        decoded_size: content.as_ref().map(Vec::len),
+        format,
+        platform,
+        capacity,
+        findings,
+    }
+}
+
+fn capacity(data: &[u8], version: usize, ecc_level: EccLevel) -> Capacity {
+    let mode = Mode::of(data);
+    let version_capacity = capacity_in(version, ecc_level, mode);
+    let max_capacity = capacity_in(BYTE_CAPACITY.len(), ecc_level, mode).unwrap_or(0);
+    let min_version = (1..=BYTE_CAPACITY.len()).find(|version| {
+        capacity_in(*version, ecc_level, mode).is_some_and(|cap| cap >= data.len())
+    });
+
+    Capacity {
+        mode,
+        version_capacity,
+        max_capacity,
+        min_version,
+        fits: version_capacity.is_some_and(|cap| cap >= data.len()),
+    }
+}
+
+fn check_platform(platform: Option<Platform>, os: &str, arch: &str) -> Vec<Finding> {
+    let Some(platform) = platform else {
+        return Vec::new();
+    };
+
+    let mut findings = Vec::new();
+    let declared_os = Os::parse(os);
+    let declared_arch = Arch::parse(arch);
+
+    match declared_os {
+        Some(declared) if declared != platform.os && platform.os != Os::Web => {
+            findings.push(Finding {
+                severity: Severity::Warning,
+                message: format!(
+                    "payload is a {:?} program but the submission says its os is `{os}`",
+                    platform.os
+                ),
+            })
+        }
+        None if !os.trim().is_empty() => findings.push(Finding {
+            severity: Severity::Info,
+            message: format!(
+                "unrecognized os `{os}`, payload looks like a {:?} program",
+                platform.os
+            ),
+        }),
+        _ => {}
+    }
+
+    if let (Some(detected), Some(declared)) = (platform.arch, declared_arch) {
+        if detected != declared {
+            findings.push(Finding {
+                severity: Severity::Warning,
+                message: format!(
+                    "payload is built for {detected:?} but the submission says its architecture is `{arch}`"
+                ),
+            });
+        }
+    }
+
+    findings
+}
+
+/// Splits a data URI into its MIME type and decoded data.
+fn parse_data_uri(uri: &str) -> Option<(String, Vec<u8>)> {
+    let uri = uri.trim();
+    let rest = uri
+        .get(..5)?
+        .eq_ignore_ascii_case("data:")
+        .then(|| &uri[5..])?;
+    let (header, data) = rest.split_once(',')?;
+
+    let (media_type, base64) = match header.strip_suffix(";base64") {
+        Some(media_type) => (media_type, true),
+        None => (header, false),
+    };
+
+    let mime = media_type
+        .split(';')
+        .next()
+        .filter(|mime| !mime.is_empty())
+        .unwrap_or("text/plain")
+        .to_ascii_lowercase();
+
+    let data = if base64 {
+        decode_base64(&percent_decode(data))?
+    } else {
+        percent_decode(data)
+    };
+
+    Some((mime, data))
+}
+
+/// Long runs of base64 text are usually an encoded program.
+fn decode_base64_text(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim();
+    if text.len() < 32 || text.contains(char::is_whitespace) {
+        return None;
+    }
+
+    decode_base64(text.as_bytes())
+}
+
+fn decode_base64(data: &[u8]) -> Option<Vec<u8>> {
+    use base64::prelude::{BASE64_STANDARD, BASE64_STANDARD_NO_PAD, BASE64_URL_SAFE};
+
+    BASE64_STANDARD
+        .decode(data)
+        .or_else(|_| BASE64_STANDARD_NO_PAD.decode(data))
+        .or_else(|_| BASE64_URL_SAFE.decode(data))
+        .ok()
+}
+
+fn percent_decode(data: &str) -> Vec<u8> {
+    let bytes = data.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| bytes.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    decoded
+}
+
+/// Guesses the format of some data from its magic bytes.
+fn sniff(data: &[u8]) -> (Format, Option<Platform>) {
+    let u16_le = |at: usize| {
+        data.get(at..at + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+    };
+    let u32_le = |at: usize| {
+        data.get(at..at + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+    let u32_be = |at: usize| {
+        data.get(at..at + 4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    };
+
+    match data {
+        [0x7f, b'E', b'L', b'F', ..] => {
+            let machine = match data.get(5) {
+                Some(2) => data.get(18..20).map(|b| u16::from_be_bytes([b[0], b[1]])),
+                _ => u16_le(18),
+            };
+            let arch = match machine {
+                Some(0x03) => Some(Arch::X86),
+                Some(0x3e) => Some(Arch::X86_64),
+                Some(0x28) => Some(Arch::Arm),
+                Some(0xb7) => Some(Arch::Aarch64),
+                Some(0xf3) => Some(Arch::RiscV),
+                _ => None,
+            };
+            (
+                Format::Elf,
+                Some(Platform {
+                    os: Os::Linux,
+                    arch,
+                }),
+            )
+        }
+        [b'M', b'Z', ..] => {
+            let machine = u32_le(0x3c)
+                .map(|offset| offset as usize)
+                .filter(|offset| data.get(*offset..*offset + 4) == Some(b"PE\0\0"))
+                .and_then(|offset| u16_le(offset + 4));
+            let arch = match machine {
+                Some(0x014c) => Some(Arch::X86),
+                Some(0x8664) => Some(Arch::X86_64),
+                Some(0x01c0 | 0x01c4) => Some(Arch::Arm),
+                Some(0xaa64) => Some(Arch::Aarch64),
+                _ => None,
+            };
+            (
+                Format::Pe,
+                Some(Platform {
+                    os: Os::Windows,
+                    arch,
+                }),
+            )
+        }
+        [0xcf, 0xfa, 0xed, 0xfe, ..] | [0xce, 0xfa, 0xed, 0xfe, ..] => {
+            let arch = match u32_le(4) {
+                Some(0x07) => Some(Arch::X86),
+                Some(0x0100_0007) => Some(Arch::X86_64),
+                Some(0x0c) => Some(Arch::Arm),
+                Some(0x0100_000c) => Some(Arch::Aarch64),
+                _ => None,
+            };
+            (
+                Format::MachO,
+                Some(Platform {
+                    os: Os::Macos,
+                    arch,
+                }),
+            )
+        }
+        [0xca, 0xfe, 0xba, 0xbe, ..] => match u32_be(4) {
+            Some(version) if version >= MIN_CLASS_FILE_VERSION => (Format::JavaClass, None),
+            _ => (
+                Format::MachO,
+                Some(Platform {
+                    os: Os::Macos,
+                    arch: None,
+                }),
+            ),
+        },
+        [0x00, b'a', b's', b'm', ..] => (
+            Format::Wasm,
+            Some(Platform {
+                os: Os::Web,
+                arch: None,
+            }),
+        ),
+        [0x1f, 0x8b, ..] => (Format::Gzip, None),
+        [0x78, 0x01 | 0x5e | 0x9c | 0xda, ..] => (Format::Zlib, None),
+        [b'P', b'K', 0x03, 0x04, ..] => (Format::Zip, None),
+        [0xfd, b'7', b'z', b'X', b'Z', 0x00, ..] => (Format::Xz, None),
+        [b'B', b'Z', b'h', ..] => (Format::Bzip2, None),
+        [0x28, 0xb5, 0x2f, 0xfd, ..] => (Format::Zstd, None),
+        [b'#', b'!', ..] => (Format::Script, None),
+        _ => match std::str::from_utf8(data) {
+            Ok(text) => {
+                let start = text.trim_start().to_ascii_lowercase();
+                if start.starts_with("<!doctype html") || start.starts_with("<html") {
+                    (
+                        Format::Html,
+                        Some(Platform {
+                            os: Os::Web,
+                            arch: None,
+                        }),
+                    )
+                } else {
+                    (Format::Text, None)
+                }
+            }
+            Err(_) => (Format::Unknown, None),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn platform(os: Os, arch: Option<Arch>) -> Option<Platform> {
+        Some(Platform { os, arch })
+    }
+
+    #[test]
+    fn sniffs_executables() {
+        let mut elf = vec![0x7f, b'E', b'L', b'F', 2, 1];
+        elf.resize(20, 0);
+        elf[18] = 0x3e;
+        assert_eq!(
+            sniff(&elf),
+            (Format::Elf, platform(Os::Linux, Some(Arch::X86_64)))
+        );
+
+        let mut pe = vec![0; 0x48];
+        pe[..2].copy_from_slice(b"MZ");
+        pe[0x3c] = 0x40;
+        pe[0x40..0x44].copy_from_slice(b"PE\0\0");
+        pe[0x44..0x46].copy_from_slice(&0xaa64u16.to_le_bytes());
+        assert_eq!(
+            sniff(&pe),
+            (Format::Pe, platform(Os::Windows, Some(Arch::Aarch64)))
+        );
+
+        let macho = [0xcf, 0xfa, 0xed, 0xfe, 0x0c, 0x00, 0x00, 0x01];
+        assert_eq!(
+            sniff(&macho),
+            (Format::MachO, platform(Os::Macos, Some(Arch::Aarch64)))
+        );
+
+        assert_eq!(
+            sniff(b"\0asm\x01\0\0\0"),
+            (Format::Wasm, platform(Os::Web, None))
+        );
+    }
+
+    #[test]
+    fn tells_fat_binaries_from_class_files() {
+        let fat = [0xca, 0xfe, 0xba, 0xbe, 0, 0, 0, 2];
+        assert_eq!(sniff(&fat), (Format::MachO, platform(Os::Macos, None)));
+
+        // Java 17, version 61.0
+        let class = [0xca, 0xfe, 0xba, 0xbe, 0, 0, 0, 61];
+        assert_eq!(sniff(&class), (Format::JavaClass, None));
+    }
+
+    #[test]
+    fn sniffs_archives_and_text() {
+        for (data, format) in [
+            (&[0x1f, 0x8b, 0x08][..], Format::Gzip),
+            (&[0x78, 0x9c], Format::Zlib),
+            (b"PK\x03\x04", Format::Zip),
+            (b"\xfd7zXZ\0", Format::Xz),
+            (b"BZh9", Format::Bzip2),
+            (&[0x28, 0xb5, 0x2f, 0xfd], Format::Zstd),
+            (b"#!/bin/sh\necho cheese", Format::Script),
+            (b"just some text", Format::Text),
+            (&[0xff, 0xfe, 0x00], Format::Unknown),
+        ] {
+            assert_eq!(sniff(data), (format, None), "{data:?}");
+        }
+
+        assert_eq!(
+            sniff(b"  <!DOCTYPE html><p>cheese</p>"),
+            (Format::Html, platform(Os::Web, None))
+        );
+    }
+
+    #[test]
+    fn parses_data_uris() {
+        assert_eq!(
+            parse_data_uri("data:text/html;base64,PGgxPmhpPC9oMT4="),
+            Some(("text/html".to_owned(), b"<h1>hi</h1>".to_vec()))
+        );
+        assert_eq!(
+            parse_data_uri("DATA:Text/HTML;charset=utf-8,%3Ch1%3Ehi%3C/h1%3E"),
+            Some(("text/html".to_owned(), b"<h1>hi</h1>".to_vec()))
+        );
+        assert_eq!(
+            parse_data_uri("data:,hello"),
+            Some(("text/plain".to_owned(), b"hello".to_vec()))
+        );
+        assert_eq!(parse_data_uri("data:text/plain;base64,!!!"), None);
+        assert_eq!(parse_data_uri("data:text/plain"), None);
+        assert_eq!(parse_data_uri("https://example.com"), None);
+        assert_eq!(parse_data_uri("dat"), None);
+    }
+
+    #[test]
+    fn percent_decodes() {
+        assert_eq!(percent_decode("a%20b%2Fc"), b"a b/c");
+        assert_eq!(percent_decode("%e2%9c%93"), "✓".as_bytes());
+        // malformed escapes are kept as they are
+        assert_eq!(percent_decode("100%"), b"100%");
+        assert_eq!(percent_decode("%zz%4"), b"%zz%4");
+    }
+
+    #[test]
+    fn byte_capacity_is_looked_up_by_version_and_level() {
+        assert_eq!(byte_capacity(1, EccLevel::L), Some(17));
+        assert_eq!(byte_capacity(1, EccLevel::H), Some(7));
+        assert_eq!(byte_capacity(40, EccLevel::L), Some(2953));
+        assert_eq!(byte_capacity(0, EccLevel::L), None);
+        assert_eq!(byte_capacity(41, EccLevel::L), None);
+    }
+
+    #[test]
+    fn capacity_depends_on_the_mode() {
+        // worked out from the data codewords of each version, covering every character count width
+        for (version, ecc_level, numeric, alphanumeric, byte) in [
+            (1, EccLevel::L, 41, 25, 17),
+            (1, EccLevel::H, 17, 10, 7),
+            (10, EccLevel::M, 513, 311, 213),
+            (27, EccLevel::Q, 1933, 1172, 805),
+            (40, EccLevel::L, 7089, 4296, 2953),
+            (40, EccLevel::H, 3057, 1852, 1273),
+        ] {
+            assert_eq!(
+                capacity_in(version, ecc_level, Mode::Numeric),
+                Some(numeric)
+            );
+            assert_eq!(
+                capacity_in(version, ecc_level, Mode::Alphanumeric),
+                Some(alphanumeric)
+            );
+            assert_eq!(capacity_in(version, ecc_level, Mode::Byte), Some(byte));
+        }
+    }
+
+    #[test]
+    fn numeric_payloads_fit_past_the_byte_capacity() {
+        let digits = "1".repeat(41);
+        let fit = capacity(digits.as_bytes(), 1, EccLevel::L);
+        assert_eq!(fit.mode, Mode::Numeric);
+        assert!(fit.fits);
+
+        let url = "HTTPS://EXAMPLE.COM/CHEESE";
+        assert_eq!(
+            capacity(url.as_bytes(), 2, EccLevel::L).mode,
+            Mode::Alphanumeric
+        );
+
+        let text = "a".repeat(18);
+        let fit = capacity(text.as_bytes(), 1, EccLevel::L);
+        assert_eq!(fit.mode, Mode::Byte);
+        assert!(!fit.fits);
+        assert_eq!(fit.min_version, Some(2));
+    }
+
+    #[test]
+    fn parses_declared_os() {
+        for (declared, os) in [
+            ("Windows 11", Some(Os::Windows)),
+            ("win32", Some(Os::Windows)),
+            ("Linux", Some(Os::Linux)),
+            ("macOS", Some(Os::Macos)),
+            ("darwin", Some(Os::Macos)),
+            ("macOS (Darwin)", Some(Os::Macos)),
+            ("OSX", Some(Os::Macos)),
+            ("Web browser", Some(Os::Web)),
+            ("TempleOS", None),
+        ] {
+            assert_eq!(Os::parse(declared), os, "{declared}");
+        }
+    }
+}
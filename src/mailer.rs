@@ -0,0 +1,187 @@
+use lettre::{
+    address::AddressError,
+    message::{header::ContentType, Attachment, Mailbox, MultiPart, SinglePart},
+    transport::smtp::authentication::Credentials,
+    AsyncFileTransport, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use thiserror::Error;
+
+use crate::{
+    config::{MailConfig, MailTransport, SmtpSecurity},
+    email::Email,
+    ICON,
+};
+
+/// Content ID that the email template uses to reference the Say Cheese icon.
+const ICON_CID: &str = "say-cheese";
+
+#[derive(Debug, Error)]
+pub enum MailError {
+    #[error("invalid email address")]
+    Address(#[from] AddressError),
+    #[error("unable to build email")]
+    Build(#[from] lettre::error::Error),
+    #[error("unable to send email over SMTP")]
+    Smtp(#[from] lettre::transport::smtp::Error),
+    #[error("unable to write email to disk")]
+    File(#[from] lettre::transport::file::Error),
+}
+
+#[derive(Clone)]
+enum Transport {
+    Smtp(AsyncSmtpTransport<Tokio1Executor>),
+    DryRun(AsyncFileTransport<Tokio1Executor>),
+}
+
+/// Sends rendered decision emails to submitters.
+#[derive(Clone)]
+pub struct Mailer {
+    from: Mailbox,
+    transport: Transport,
+}
+
+impl Mailer {
+    pub fn new(config: &MailConfig) -> Result<Mailer, MailError> {
+        let transport = match &config.transport {
+            MailTransport::Smtp {
+                host,
+                port,
+                security,
+                credentials,
+            } => {
+                let mut builder = match security {
+                    SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?,
+                    SmtpSecurity::StartTls => {
+                        AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?
+                    }
+                    SmtpSecurity::None => {
+                        AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host)
+                    }
+                };
+
+                if let Some(port) = port {
+                    builder = builder.port(*port);
+                }
+
+                if let Some((username, password)) = credentials {
+                    builder = builder
+                        .credentials(Credentials::new(username.to_owned(), password.to_owned()));
+                }
+
+                Transport::Smtp(builder.build())
+            }
+            MailTransport::DryRun(dir) => Transport::DryRun(AsyncFileTransport::new(dir)),
+        };
+
+        Ok(Mailer {
+            from: config.from.parse()?,
+            transport,
+        })
+    }
+
+    /// Builds the MIME message for an email.
+    ///
+    /// The HTML body is wrapped in a `multipart/related` part together with
+    /// the Say Cheese icon, so that `cid:say-cheese` resolves without any remote images.
+    pub fn build(&self, to: Mailbox, email: &Email) -> Result<Message, MailError> {
+        let icon = Attachment::new_inline(ICON_CID.to_owned()).body(
+            ICON.to_vec(),
+            ContentType::parse("image/png").expect("image/png should be a valid content type"),
+        );
+
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(&email.subject)
+            .multipart(
+                MultiPart::alternative()
+                    .singlepart(SinglePart::plain(email.text.clone()))
+                    .multipart(
+                        MultiPart::related()
+                            .singlepart(SinglePart::html(email.html.clone()))
+                            .singlepart(icon),
+                    ),
+            )?;
+
+        Ok(message)
+    }
+
+    pub fn uses_smtp(&self) -> bool {
+        matches!(self.transport, Transport::Smtp(_))
+    }
+
+    /// Checks that the SMTP server accepts a connection. Dry runs always succeed.
+    pub async fn test_connection(&self) -> Result<bool, MailError> {
+        match &self.transport {
+            Transport::Smtp(smtp) => Ok(smtp.test_connection().await?),
+            Transport::DryRun(_) => Ok(true),
+        }
+    }
+
+    pub async fn send(&self, to: Mailbox, email: &Email) -> Result<(), MailError> {
+        let message = self.build(to, email)?;
+
+        match &self.transport {
+            Transport::Smtp(smtp) => {
+                smtp.send(message).await?;
+            }
+            Transport::DryRun(file) => {
+                let id = file.send(message).await?;
+                tracing::info!("wrote email {id}.eml instead of sending it");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn embeds_icon_in_related_part() {
+        let mailer = Mailer::new(&MailConfig {
+            from: "Say Cheese <saycheese@example.com>".to_owned(),
+            transport: MailTransport::DryRun(PathBuf::from("emails")),
+        })
+        .unwrap();
+        let email = Email {
+            subject: "Say Cheese: Pocket Tetris has been accepted!".to_owned(),
+            html: r#"<img src="cid:say-cheese"><p>Congratulations</p>"#.to_owned(),
+            text: "Congratulations".to_owned(),
+        };
+
+        let message = mailer
+            .build("Ada <ada@example.com>".parse().unwrap(), &email)
+            .unwrap();
+        let formatted = String::from_utf8(message.formatted()).unwrap();
+
+        // every part in the order it's nested: the text and the related part are
+        // alternatives, and the icon sits next to the HTML that references it
+        let parts = [
+            "Content-Type: multipart/alternative",
+            "Content-Type: text/plain",
+            "Content-Type: multipart/related",
+            "Content-Type: text/html",
+            "Content-Type: image/png",
+        ];
+        let positions: Vec<usize> = parts
+            .iter()
+            .map(|part| {
+                formatted
+                    .find(part)
+                    .unwrap_or_else(|| panic!("missing `{part}` in:\n{formatted}"))
+            })
+            .collect();
+        assert!(positions.is_sorted(), "parts out of order in:\n{formatted}");
+
+        // headers of the icon part come after the HTML part, in no particular order
+        let icon = &formatted[positions[3]..];
+        assert!(icon.contains("Content-ID: <say-cheese>"), "{formatted}");
+        assert!(icon.contains("Content-Disposition: inline"), "{formatted}");
+        assert_eq!(formatted.matches("Content-Type: image/png").count(), 1);
+    }
+}
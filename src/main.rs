@@ -1,27 +1,66 @@
 use std::fs::File;
 
 use actix_files::{Files, NamedFile};
-use actix_web::{get, middleware::Logger, post, web, App, HttpResponse, HttpServer, Responder};
-use airtable::{
+use actix_web::{
+    delete, get, middleware, post, web, App, HttpRequest, HttpResponse, HttpServer, Responder,
+};
+use audit::{Actor, AuditAction, AuditLog};
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use claims::Claims;
+use clap::Parser;
+use config::Config;
+use error::AppError;
+use events::{Event, Events};
+use hooks::Hooks;
+use mailer::Mailer;
+use mirror::{Mirror, Store};
+use outbox::{DeliveryState, Outbox};
+use saycheese_review::airtable::{
+    self,
     api::{ListRecords, Record, RecordId},
     Attachment, Base,
 };
-use base64::Engine;
 use serde::{Deserialize, Serialize};
-use serde_json::json;
+use status::Status;
 
-mod airtable;
+mod audit;
+mod bundle;
+mod claims;
+mod cli;
+mod config;
+mod decision;
+mod email;
+mod error;
+mod events;
+mod export;
+mod health;
+mod hooks;
+mod inspect;
+mod mailer;
+mod manifest;
+mod metrics;
+mod mirror;
+mod outbox;
+mod qr;
+mod records;
+mod resubmit;
+mod site;
+mod stats;
+mod status;
+mod telemetry;
+mod undo;
 
 const AIRTABLE_API_KEY: &str = env!("AIRTABLE_API_KEY");
 const AIRTABLE_BASE_ID: &str = env!("AIRTABLE_BASE_ID");
 const ICON: &[u8; 76109] = include_bytes!("../static/say-cheese.png");
-const EMAIL: &str = include_str!("../static/email.html");
 const IMAGE_DATA_URI: &str = "data:image/png;base64,";
+const STATIC_DIR: &str = "static";
 
 const SUBMISSION_TABLE: &str = "YSWS Project Submission";
 const TABLE_VIEW: &str = "Grid View";
 
-const FIELDS: [&str; 12] = [
+const FIELDS: [&str; 13] = [
     "project_name",
     "Code URL",
     "Screenshot",
@@ -34,6 +73,7 @@ const FIELDS: [&str; 12] = [
     "architecture",
     "status",
     "email_message",
+    "resubmit_deadline",
 ];
 
 // listen i didnt name the records dont blame me
@@ -57,61 +97,115 @@ struct Submission {
     architecture: String,
     #[serde(default)]
     email_message: String,
-    #[serde(default = "default_status")]
-    status: String,
+    #[serde(default)]
+    status: Status,
+    /// When a rejected submission stops accepting resubmissions.
+    #[serde(default)]
+    resubmit_deadline: Option<DateTime<Utc>>,
 }
 
-fn default_status() -> String {
-    "new".to_owned()
+/// A submission along with what was found in its QR code,
+/// so problems with the payload are visible before reviewing.
+#[derive(Serialize)]
+struct ReviewRecord {
+    #[serde(flatten)]
+    record: Record<Submission>,
+    qr: inspect::QrReport,
 }
 
-#[get("/record/{i}")]
-async fn record(i: web::Path<usize>) -> impl Responder {
-    let json: Vec<Record<Submission>> =
-        ListRecords::new(AIRTABLE_BASE_ID.to_owned(), SUBMISSION_TABLE.to_owned())
-            .with_view(TABLE_VIEW.to_owned())
-            .with_fields(FIELDS.iter().map(ToString::to_string).collect())
-            .request(AIRTABLE_API_KEY)
-            .await
-            .unwrap();
+impl ReviewRecord {
+    async fn inspect(rec: Record<Submission>) -> ReviewRecord {
+        let qr = inspect::inspect_submission(rec.fields()).await;
+        ReviewRecord { record: rec, qr }
+    }
+}
+
+/// Fetches a single submission, returning 404 if there's no record with this ID.
+///
+/// Airtable returns every field when getting a single record,
+/// [`Submission`] keeps the ones in [`FIELDS`]. With the mirror enabled
+/// recently synced records are served locally.
+#[get("/record/{id}")]
+async fn record(
+    id: web::Path<RecordId>,
+    store: web::Data<Store>,
+) -> Result<impl Responder, AppError> {
+    let rec = store.get(&id).await?;
+
+    Ok(web::Json(ReviewRecord::inspect(rec).await))
+}
 
-    json.get(i.into_inner()).cloned().map(web::Json)
+/// Lists submissions in the review queue, see [`records::RecordQuery`] for the filters.
+#[get("/records")]
+async fn list_records(query: web::Query<records::RecordQuery>) -> Result<impl Responder, AppError> {
+    Ok(web::Json(records::list(query.into_inner()).await?))
 }
 
+/// Hands out the oldest submission in the queue that no other reviewer has claimed, and claims it.
+///
+/// Claims are held per reviewer, so requests without a [`audit::REVIEWER_HEADER`] are refused
+/// rather than letting everyone who leaves it out share one claim.
 #[get("/nextrecord")]
-async fn next_record() -> impl Responder {
-    let records: Vec<Record<Submission>> =
-        ListRecords::new(AIRTABLE_BASE_ID.to_owned(), SUBMISSION_TABLE.to_owned())
-            .with_view(TABLE_VIEW.to_owned())
-            .with_fields(FIELDS.iter().map(ToString::to_string).collect())
-            .with_filter_by_formula("status = \"new\"".to_owned())
-            .request(AIRTABLE_API_KEY)
-            .await
-            .unwrap();
+async fn next_record(
+    req: HttpRequest,
+    store: web::Data<Store>,
+    claims: web::Data<Claims>,
+    events: web::Data<Events>,
+) -> Result<impl Responder, AppError> {
+    let reviewer = audit::reviewer(&req).ok_or_else(|| {
+        AppError::BadRequest(format!(
+            "`{}` is required to be handed a submission",
+            audit::REVIEWER_HEADER
+        ))
+    })?;
 
-    match records.first() {
-        Some(submission) => return HttpResponse::Ok().json(submission),
-        None => {
-            return HttpResponse::NotFound().json(json!(
-                r#"{"status": 404, "message": "No additional submissions to review."}"#
-            ))
+    let rec = loop {
+        let rec = store
+            .next_in_queue(&claims.held_by_others(&reviewer))
+            .await?
+            .ok_or_else(|| AppError::NotFound("No additional submissions to review.".to_owned()))?;
+
+        // someone else may have claimed it in the meantime
+        if claims.claim(rec.id(), &reviewer) {
+            break rec;
         }
-    }
+    };
+
+    events.publish(Event::SubmissionClaimed {
+        id: rec.id().clone(),
+        reviewer,
+    });
+
+    Ok(web::Json(ReviewRecord::inspect(rec).await))
+}
+
+/// Streams [`Event`]s about the review queue as server-sent events.
+#[get("/events")]
+async fn event_stream(
+    store: web::Data<Store>,
+    events: web::Data<Events>,
+) -> Result<impl Responder, AppError> {
+    let count = store.queue_count().await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(events.stream(vec![Event::QueueCount { count }])))
 }
 
 #[get("/test")]
-async fn test(base: web::Data<Base>) -> impl Responder {
-    let data = base.query().await.unwrap();
-    let file = File::create("records.json").unwrap();
-    serde_json::to_writer_pretty(file, &data).unwrap();
+async fn test(base: web::Data<Base>) -> Result<impl Responder, AppError> {
+    let data = base.query().await?;
+    let file = File::create("records.json")?;
+    serde_json::to_writer_pretty(file, &data)?;
 
-    HttpResponse::Ok()
+    Ok(HttpResponse::Ok()
         .content_type("application/json")
-        .body(r#"{"status": 200, "message": "wrote data to disk"}"#)
+        .body(r#"{"status": 200, "message": "wrote data to disk"}"#))
 }
 
 #[post("/update")]
-async fn update(submission: web::Json<Record<Submission>>) -> impl Responder {
+async fn update(_submission: web::Json<Record<Submission>>) -> impl Responder {
     // base.update_records(&[submission.into_inner()]).await.unwrap();
 
     HttpResponse::Ok()
@@ -124,60 +218,393 @@ async fn icon_uri() -> impl Responder {
     let icon = base64::prelude::BASE64_STANDARD.encode(ICON);
     let uri = IMAGE_DATA_URI.to_owned() + &icon;
 
-    HttpResponse::Ok()
-        .content_type("text/plain")
-        .body(uri)
+    HttpResponse::Ok().content_type("text/plain").body(uri)
 }
 
 #[derive(Deserialize)]
 struct ReviewData {
     id: RecordId,
-    status: String,
+    status: Status,
     message: String,
 }
 
+/// Records a decision and, if email is configured, queues the decision email.
 #[post("/review")]
-async fn review(submission: web::Json<ReviewData>) -> impl Responder {
-    println!("hi!");
+#[allow(clippy::too_many_arguments)]
+async fn review(
+    submission: web::Json<ReviewData>,
+    config: web::Data<Config>,
+    outbox: web::Data<Outbox>,
+    audit: web::Data<AuditLog>,
+    store: web::Data<Store>,
+    claims: web::Data<Claims>,
+    events: web::Data<Events>,
+    actor: Actor,
+) -> Result<impl Responder, AppError> {
+    let submission = submission.into_inner();
+    let decision = decision::decide(
+        &submission.id,
+        submission.status,
+        submission.message,
+        &config,
+        &store,
+        &audit,
+        &outbox,
+        &actor,
+    )
+    .await?;
+    let updated = decision.record;
 
-    let rec: Record<Submission> = airtable::api::get_record(AIRTABLE_API_KEY, AIRTABLE_BASE_ID, SUBMISSION_TABLE, &submission.id).await.unwrap();
-    let mut data = rec.into_fields();
-    data.status = submission.status.clone();
-    data.email_message = submission.message.clone();
+    claims.release(updated.id());
+    events.publish(Event::SubmissionReviewed {
+        id: updated.id().clone(),
+        reviewer: actor.reviewer.clone(),
+        status: updated.fields().status,
+    });
+    events::spawn_queue_count(events, store);
+
+    if !decision.email_queued {
+        return Ok(HttpResponse::Ok()
+            .content_type("application/json")
+            .body(r#"{"status": 200, "message": "updated submission"}"#));
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(r#"{"status": 200, "message": "updated submission and queued email"}"#))
+}
+
+/// Reverts a review made in the last [`undo::UNDO_WINDOW`] and cancels its email.
+#[post("/review/{id}/undo")]
+async fn undo_review(
+    id: web::Path<RecordId>,
+    outbox: web::Data<Outbox>,
+    audit: web::Data<AuditLog>,
+    store: web::Data<Store>,
+    events: web::Data<Events>,
+    actor: Actor,
+) -> Result<impl Responder, AppError> {
+    let report = undo::undo_review(&id, &store, &audit, &outbox, &actor).await?;
+    events::spawn_queue_count(events, store);
+
+    Ok(web::Json(report))
+}
+
+#[derive(Deserialize)]
+struct OutboxFilter {
+    state: Option<DeliveryState>,
+}
+
+#[get("/admin/outbox")]
+async fn list_outbox(
+    outbox: web::Data<Outbox>,
+    filter: web::Query<OutboxFilter>,
+) -> Result<impl Responder, AppError> {
+    Ok(web::Json(outbox.list(filter.state)?))
+}
+
+/// Queues a failed (or still pending) email to be sent again right away.
+#[post("/admin/outbox/{id}/resend")]
+async fn resend_email(
+    outbox: web::Data<Outbox>,
+    id: web::Path<i64>,
+) -> Result<impl Responder, AppError> {
+    let id = id.into_inner();
+    let message = outbox
+        .get(id)?
+        .ok_or_else(|| AppError::NotFound(format!("no email with id {id}")))?;
+
+    if !outbox.resend(message.id)? {
+        return Err(AppError::Conflict(format!(
+            "email {id} is `{}`, only pending or failed emails can be resent",
+            message.state.as_str()
+        )));
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(r#"{"status": 200, "message": "queued email"}"#))
+}
 
-    airtable::api::update_record(AIRTABLE_API_KEY, AIRTABLE_BASE_ID, SUBMISSION_TABLE, &submission.id, data, false).await.unwrap();
+/// Decodes and inspects the QR code attached to a submission.
+#[get("/record/{id}/qr")]
+async fn record_qr(
+    id: web::Path<RecordId>,
+    store: web::Data<Store>,
+) -> Result<impl Responder, AppError> {
+    let rec = store.get(&id).await?;
+
+    Ok(web::Json(inspect::inspect_submission(rec.fields()).await))
+}
+
+/// Builds the gallery bundle for a submission.
+#[get("/record/{id}/bundle.zip")]
+async fn record_bundle(
+    id: web::Path<RecordId>,
+    store: web::Data<Store>,
+) -> Result<impl Responder, AppError> {
+    let rec = store.get(&id).await?;
+    let bundle = bundle::Bundle::fetch(&rec).await?.into_zip().await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/zip")
+        .insert_header((
+            "Content-Disposition",
+            r#"attachment; filename="project.zip""#,
+        ))
+        .body(bundle))
+}
+
+/// Syncs the mirror with Airtable right away instead of waiting for the next poll.
+#[post("/admin/mirror/sync")]
+async fn sync_mirror(store: web::Data<Store>) -> Result<impl Responder, AppError> {
+    let mirror = store.mirror().ok_or_else(|| {
+        AppError::BadRequest("the mirror is disabled, set `AIRTABLE_MIRROR=true`".to_owned())
+    })?;
+
+    Ok(web::Json(mirror.sync().await?))
+}
+
+/// Receives change notifications from the webhooks created through `/admin/webhooks`.
+#[post("/hooks/airtable")]
+async fn airtable_hook(
+    req: HttpRequest,
+    body: web::Bytes,
+    hooks: web::Data<Hooks>,
+    store: web::Data<Store>,
+    events: web::Data<Events>,
+) -> Result<impl Responder, AppError> {
+    let notification: airtable::webhooks::Notification = serde_json::from_slice(&body)
+        .map_err(|err| AppError::BadRequest(format!("invalid notification: {err}")))?;
+
+    let mac = req
+        .headers()
+        .get(airtable::webhooks::MAC_HEADER)
+        .and_then(|mac| mac.to_str().ok())
+        .unwrap_or_default();
+    hooks.verify(&notification.webhook.id, &body, mac)?;
+
+    if notification.base.id != AIRTABLE_BASE_ID {
+        return Err(AppError::BadRequest(format!(
+            "notification is for another base, {}",
+            notification.base.id
+        )));
+    }
+
+    let report = hooks
+        .handle(&notification.webhook.id, &store, &events)
+        .await?;
+    tracing::info!(
+        "webhook {}: {} payloads, {} created, {} changed, {} destroyed",
+        notification.webhook.id,
+        report.payloads,
+        report.created,
+        report.changed,
+        report.destroyed
+    );
+
+    Ok(web::Json(report))
+}
 
+/// Webhooks as Airtable sees them, including ones created elsewhere.
+#[get("/admin/webhooks")]
+async fn list_webhooks() -> Result<impl Responder, AppError> {
+    Ok(web::Json(
+        airtable::webhooks::list_webhooks(AIRTABLE_API_KEY, AIRTABLE_BASE_ID).await?,
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+struct NewWebhook {
+    /// The public address of `/hooks/airtable`.
+    notification_url: String,
+    /// Only notify about changes to this table, e.g. `tblAbCdEfGh012345`.
+    table_id: Option<String>,
+}
+
+#[post("/admin/webhooks")]
+async fn create_webhook(
+    hooks: web::Data<Hooks>,
+    webhook: web::Json<NewWebhook>,
+) -> Result<impl Responder, AppError> {
+    Ok(web::Json(
+        hooks
+            .create(&webhook.notification_url, webhook.table_id.as_deref())
+            .await?,
+    ))
+}
+
+#[delete("/admin/webhooks/{id}")]
+async fn delete_webhook(
+    hooks: web::Data<Hooks>,
+    id: web::Path<String>,
+) -> Result<impl Responder, AppError> {
+    hooks.delete(&id).await?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(r#"{"status": 200, "message": "webhook deleted"}"#))
+}
+
+/// Submission counts, reviewer throughput and backlog age, recomputed at most every [`stats::STATS_TTL`].
+#[get("/stats")]
+async fn review_stats(
+    cache: web::Data<stats::StatsCache>,
+    store: web::Data<Store>,
+    audit: web::Data<AuditLog>,
+) -> Result<impl Responder, AppError> {
+    Ok(web::Json(cache.get(&store, &audit).await?))
+}
+
+/// Responds as long as the process is up.
+#[get("/healthz")]
+async fn healthz() -> impl Responder {
     HttpResponse::Ok()
         .content_type("application/json")
-        .body(r#"{"status": 200, "message": "updated submission"}"#)
+        .body(r#"{"status": 200, "message": "ok"}"#)
+}
+
+/// Whether Airtable, SMTP and the static files are all usable, with how long each check took.
+#[get("/readyz")]
+async fn readyz(mailer: web::Data<Option<Mailer>>) -> impl Responder {
+    let readiness = health::readiness(mailer.as_ref().as_ref()).await;
+
+    match readiness.ready {
+        true => HttpResponse::Ok().json(readiness),
+        false => HttpResponse::ServiceUnavailable().json(readiness),
+    }
+}
+
+/// Operational metrics in the Prometheus text format.
+#[get("/metrics")]
+async fn prometheus_metrics(
+    store: web::Data<Store>,
+    outbox: web::Data<Outbox>,
+) -> Result<impl Responder, AppError> {
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(metrics::render(&store, &outbox).await?))
+}
+
+/// Hit and miss counts of the Airtable listing cache.
+#[get("/admin/cache")]
+async fn cache_stats() -> impl Responder {
+    web::Json(airtable::cache::cache().stats())
+}
+
+/// Exports every accepted submission that isn't in the gallery yet to `EXPORT_DIR`.
+#[post("/admin/export")]
+async fn export_gallery(
+    config: web::Data<Config>,
+    audit: web::Data<AuditLog>,
+    actor: Actor,
+) -> Result<impl Responder, AppError> {
+    Ok(web::Json(
+        export::export(&config.export_dir, &audit, &actor).await?,
+    ))
+}
+
+/// JSON Schema of the `proj.json` manifest in each gallery bundle.
+#[get("/gallery/manifest.schema.json")]
+async fn manifest_schema() -> impl Responder {
+    web::Json(manifest::Manifest::schema())
+}
+
+/// Checks a `proj.json` manifest, listing every problem with it.
+#[post("/gallery/validate")]
+async fn validate_manifest(manifest: web::Json<manifest::Manifest>) -> impl Responder {
+    match manifest.validate() {
+        Ok(()) => HttpResponse::Ok()
+            .content_type("application/json")
+            .body(r#"{"status": 200, "message": "manifest is valid"}"#),
+        Err(errors) => HttpResponse::UnprocessableEntity().json(errors),
+    }
+}
+
+#[derive(Deserialize)]
+struct PreviewOptions {
+    status: Option<Status>,
+    message: Option<String>,
+}
+
+/// Renders the decision email for a submission.
+/// `status` and `message` can be given to preview a decision before it is made.
+#[get("/preview-email/{id}")]
+async fn preview_email(
+    id: web::Path<RecordId>,
+    options: web::Query<PreviewOptions>,
+    store: web::Data<Store>,
+) -> Result<impl Responder, AppError> {
+    let rec = store.get(&id).await?;
+    let mut data = rec.into_fields();
+    let options = options.into_inner();
+
+    if let Some(status) = options.status {
+        data.status = status;
+    }
+
+    if let Some(message) = options.message {
+        data.email_message = message;
+    }
+
+    Ok(web::Json(email::render(&data)?))
+}
+
+#[get("/resubmissions")]
+async fn resubmissions() -> Result<impl Responder, AppError> {
+    Ok(web::Json(resubmit::open_windows().await?))
+}
+
+#[post("/resubmissions/sweep")]
+async fn sweep_resubmissions(
+    audit: web::Data<AuditLog>,
+    actor: Actor,
+) -> Result<impl Responder, AppError> {
+    Ok(web::Json(resubmit::sweep(&audit, &actor).await?))
+}
+
+#[derive(Deserialize)]
+struct AuditFilter {
+    record: Option<RecordId>,
+}
+
+/// Lists changes made to submissions, newest first.
+#[get("/audit")]
+async fn list_audit(
+    audit: web::Data<AuditLog>,
+    filter: web::Query<AuditFilter>,
+) -> Result<impl Responder, AppError> {
+    Ok(web::Json(audit.list(filter.record.as_ref())?))
 }
 
 #[get("/updatetest")]
-async fn update_test() -> impl Responder {
+async fn update_test(audit: web::Data<AuditLog>, actor: Actor) -> Result<impl Responder, AppError> {
     let records: Vec<Record<Submission>> =
         ListRecords::new(AIRTABLE_BASE_ID.to_owned(), SUBMISSION_TABLE.to_owned())
             .with_view(TABLE_VIEW.to_owned())
             .request(AIRTABLE_API_KEY)
-            .await
-            .unwrap();
+            .await?;
 
-    let mut test_record = records[68].fields().clone();
-    test_record.status = "accepted".to_owned();
+    let rec = records
+        .get(68)
+        .ok_or_else(|| AppError::NotFound("no submission at position 68".to_owned()))?;
+    let mut test_record = rec.fields().clone();
+    test_record.status = Status::Accepted;
 
-    airtable::api::update_record(
+    let changes = audit::diff(Some(rec.fields()), &test_record)?;
+    let _: Record<serde_json::Map<String, serde_json::Value>> = airtable::api::update_record(
         AIRTABLE_API_KEY,
         AIRTABLE_BASE_ID,
         SUBMISSION_TABLE,
-        records[68].id(),
-        test_record,
+        rec.id(),
+        audit::patch(&changes),
         false,
     )
-    .await
-    .unwrap();
+    .await?;
+    audit.record(&actor, rec.id(), AuditAction::Test, &changes)?;
 
-    HttpResponse::Ok()
+    Ok(HttpResponse::Ok()
         .content_type("application/json")
-        .body(r#"{"status": 200,"message":"updated record"}"#)
+        .body(r#"{"status": 200,"message":"updated record"}"#))
 }
 
 #[get("/")]
@@ -192,9 +619,47 @@ async fn favicon() -> impl Responder {
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    std::env::set_var("RUST_LOG", "debug");
-    std::env::set_var("RUST_LOG", "actix_web=trace");
-    env_logger::init();
+    let cli = cli::Cli::parse();
+    let config = Config::from_env()
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+    telemetry::init(&config)
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+    airtable::cache::cache().set_ttl(config.cache_ttl);
+
+    match cli.command {
+        None | Some(cli::Command::Serve) => {}
+        Some(command) => {
+            return cli::run(command, config)
+                .await
+                .map_err(std::io::Error::other)
+        }
+    }
+
+    let audit = web::Data::new(AuditLog::open(&config.database).map_err(std::io::Error::other)?);
+
+    let mailer = match &config.mail {
+        Some(mail) => Some(
+            Mailer::new(mail)
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?,
+        ),
+        None => {
+            tracing::warn!("EMAIL_FROM is not set, decision emails will not be sent");
+            None
+        }
+    };
+
+    let outbox = web::Data::new(Outbox::open(&config.database).map_err(std::io::Error::other)?);
+
+    if let Some(mailer) = mailer.clone() {
+        actix_web::rt::spawn(outbox::deliver_periodically(
+            outbox.clone(),
+            mailer,
+            audit.clone(),
+        ));
+    }
+    let mailer = web::Data::new(mailer);
+
+    let config = web::Data::new(config);
 
     let base = Base::new(
         AIRTABLE_API_KEY.to_owned(),
@@ -203,12 +668,49 @@ async fn main() -> std::io::Result<()> {
         TABLE_VIEW,
     );
 
+    actix_web::rt::spawn(resubmit::sweep_periodically(audit.clone()));
+
+    let mirror = match config.mirror {
+        true => Some(Mirror::open(&config.database).map_err(std::io::Error::other)?),
+        false => None,
+    };
+    let store = web::Data::new(Store::new(mirror));
+    actix_web::rt::spawn(mirror::sync_periodically(store.clone()));
+
+    let hooks = web::Data::new(Hooks::open(&config.database).map_err(std::io::Error::other)?);
+    actix_web::rt::spawn(hooks::refresh_periodically(hooks.clone()));
+
+    let claims = web::Data::new(Claims::default());
+    let events = web::Data::new(Events::default());
+    let stats = web::Data::new(stats::StatsCache::default());
+
     HttpServer::new(move || {
         App::new()
-            .wrap(Logger::default())
+            .wrap(middleware::from_fn(metrics::track))
+            .wrap(middleware::from_fn(telemetry::trace_request))
             .app_data(web::Data::new(base.clone()))
+            .app_data(config.clone())
+            .app_data(outbox.clone())
+            .app_data(audit.clone())
+            .app_data(store.clone())
+            .app_data(hooks.clone())
+            .app_data(claims.clone())
+            .app_data(events.clone())
+            .app_data(stats.clone())
+            .app_data(mailer.clone())
+            .app_data(web::JsonConfig::default().error_handler(|err, _| {
+                AppError::BadRequest(format!("invalid request body: {err}")).into()
+            }))
+            .app_data(web::PathConfig::default().error_handler(|err, _| {
+                AppError::BadRequest(format!("invalid path: {err}")).into()
+            }))
+            .app_data(web::QueryConfig::default().error_handler(|err, _| {
+                AppError::BadRequest(format!("invalid query: {err}")).into()
+            }))
             .service(record)
             .service(next_record)
+            .service(event_stream)
+            .service(list_records)
             .service(index)
             .service(favicon)
             .service(test)
@@ -216,7 +718,29 @@ async fn main() -> std::io::Result<()> {
             .service(update_test)
             .service(icon_uri)
             .service(review)
-            .service(Files::new("/static", "static").prefer_utf8(true))
+            .service(undo_review)
+            .service(preview_email)
+            .service(record_qr)
+            .service(record_bundle)
+            .service(manifest_schema)
+            .service(export_gallery)
+            .service(sync_mirror)
+            .service(cache_stats)
+            .service(review_stats)
+            .service(prometheus_metrics)
+            .service(healthz)
+            .service(readyz)
+            .service(airtable_hook)
+            .service(list_webhooks)
+            .service(create_webhook)
+            .service(delete_webhook)
+            .service(validate_manifest)
+            .service(list_outbox)
+            .service(resend_email)
+            .service(resubmissions)
+            .service(sweep_resubmissions)
+            .service(list_audit)
+            .service(Files::new("/static", STATIC_DIR).prefer_utf8(true))
     })
     .bind(("127.0.0.1", 8080))?
     .run()
@@ -0,0 +1,301 @@
+use saycheese_review::airtable::api::Record;
+use schemars::{JsonSchema, Schema};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use url::Url;
+
+use crate::{qr::PayloadKind, Submission};
+
+/// Version of the `proj.json` layout, bumped whenever the gallery format changes.
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// The `proj.json` file that the gallery reads from each bundle.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// Layout version, see [`MANIFEST_VERSION`].
+    pub version: u32,
+    /// Name of the project.
+    pub name: String,
+    /// How the submitter wants to be credited in the gallery.
+    pub author: String,
+    pub qr: ManifestQr,
+    /// File name of the screenshot inside the bundle.
+    pub demo: String,
+    /// Operating system the project runs on.
+    pub os: String,
+    /// CPU architecture the project runs on.
+    pub arch: String,
+    pub description: String,
+    /// URL of the project's source code.
+    pub repo: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestQr {
+    /// File name of the QR code image inside the bundle.
+    pub image: String,
+    #[serde(rename = "type")]
+    pub ty: QrType,
+    /// The decoded contents of the QR code.
+    pub url: String,
+}
+
+/// How the gallery should treat the contents of a QR code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum QrType {
+    /// An `http` or `https` link, which the gallery links to.
+    #[serde(rename = "URL")]
+    Url,
+    /// Anything else, including `data:` URIs holding the whole project,
+    /// which the gallery only shows as text.
+    #[serde(rename = "TEXT")]
+    Text,
+}
+
+impl From<PayloadKind> for QrType {
+    fn from(kind: PayloadKind) -> Self {
+        match kind {
+            PayloadKind::Url => QrType::Url,
+            PayloadKind::DataUri | PayloadKind::Text | PayloadKind::Binary => QrType::Text,
+        }
+    }
+}
+
+/// A problem found by [`Manifest::validate`].
+#[derive(Debug, Clone, Error, Serialize)]
+#[error("`{field}` {problem}")]
+pub struct ManifestError {
+    pub field: &'static str,
+    pub problem: String,
+}
+
+impl ManifestError {
+    fn new(field: &'static str, problem: impl Into<String>) -> ManifestError {
+        ManifestError {
+            field,
+            problem: problem.into(),
+        }
+    }
+}
+
+/// Builds a manifest from a submission.
+///
+/// The QR code has to be decoded to fill in `qr.type` and `qr.url`, so `qr.url` is left empty here
+/// and the manifest won't pass [`Manifest::validate`] until it is set.
+impl From<&Record<Submission>> for Manifest {
+    fn from(rec: &Record<Submission>) -> Self {
+        let submission = rec.fields();
+
+        let qr_image = submission
+            .qr_code
+            .first()
+            .and_then(|qr_code| extension(qr_code.filename()))
+            .unwrap_or("png");
+        let demo = match submission
+            .screenshot
+            .first()
+            .and_then(|screenshot| extension(screenshot.filename()))
+        {
+            Some(extension) => format!("proj.{extension}"),
+            None => "proj".to_owned(),
+        };
+
+        Manifest {
+            version: MANIFEST_VERSION,
+            name: submission.name.clone(),
+            author: submission.gallery_attribution.clone(),
+            qr: ManifestQr {
+                image: format!("qr.{qr_image}"),
+                ty: QrType::Url,
+                url: String::new(),
+            },
+            demo,
+            os: submission.os.clone(),
+            arch: submission.architecture.clone(),
+            description: submission.description.clone(),
+            repo: submission.repo_url.clone(),
+        }
+    }
+}
+
+impl From<Record<Submission>> for Manifest {
+    fn from(rec: Record<Submission>) -> Self {
+        Manifest::from(&rec)
+    }
+}
+
+impl Manifest {
+    /// JSON Schema describing `proj.json`.
+    pub fn schema() -> Schema {
+        schemars::schema_for!(Manifest)
+    }
+
+    /// Checks everything the schema can't express,
+    /// returning every problem rather than stopping at the first.
+    pub fn validate(&self) -> Result<(), Vec<ManifestError>> {
+        let mut errors = Vec::new();
+
+        if self.version == 0 || self.version > MANIFEST_VERSION {
+            errors.push(ManifestError::new(
+                "version",
+                format!(
+                    "must be between 1 and {MANIFEST_VERSION}, found {}",
+                    self.version
+                ),
+            ));
+        }
+
+        for (field, value) in [
+            ("name", &self.name),
+            ("author", &self.author),
+            ("os", &self.os),
+            ("arch", &self.arch),
+            ("description", &self.description),
+        ] {
+            if value.trim().is_empty() {
+                errors.push(ManifestError::new(field, "must not be empty"));
+            }
+        }
+
+        for (field, value) in [("qr.image", &self.qr.image), ("demo", &self.demo)] {
+            if let Err(problem) = check_file_name(value) {
+                errors.push(ManifestError::new(field, problem));
+            }
+        }
+
+        if self.qr.image == self.demo {
+            errors.push(ManifestError::new(
+                "demo",
+                "must not be the same file as `qr.image`",
+            ));
+        }
+
+        if self.qr.url.is_empty() {
+            errors.push(ManifestError::new("qr.url", "must not be empty"));
+        } else if self.qr.ty == QrType::Url {
+            if let Err(problem) = check_http_url(&self.qr.url) {
+                errors.push(ManifestError::new("qr.url", problem));
+            }
+        }
+
+        if let Err(problem) = check_http_url(&self.repo) {
+            errors.push(ManifestError::new("repo", problem));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// The gallery links to these URLs, so anything a browser would run, like `javascript:`, is refused.
+fn check_http_url(url: &str) -> Result<(), String> {
+    match Url::parse(url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(()),
+        Ok(url) => Err(format!(
+            "must be an http or https URL, found `{}:`",
+            url.scheme()
+        )),
+        Err(err) => Err(format!("is not a valid URL: {err}")),
+    }
+}
+
+/// Files are referenced relative to `proj.json`, so they can't leave the bundle.
+fn check_file_name(name: &str) -> Result<(), &'static str> {
+    if name.is_empty() {
+        Err("must not be empty")
+    } else if name.contains(['/', '\\']) || name == "." || name == ".." {
+        Err("must be a file name in the root of the bundle")
+    } else if name == "proj.json" {
+        Err("must not overwrite `proj.json`")
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns the extension of an attachment's file name, without the dot.
+fn extension(filename: &str) -> Option<&str> {
+    filename
+        .rsplit_once('.')
+        .map(|(_, extension)| extension)
+        .filter(|extension| !extension.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(ty: QrType, url: &str) -> Manifest {
+        Manifest {
+            version: MANIFEST_VERSION,
+            name: "Cheese Grater".to_owned(),
+            author: "Kestrel".to_owned(),
+            qr: ManifestQr {
+                image: "qr.png".to_owned(),
+                ty,
+                url: url.to_owned(),
+            },
+            demo: "proj.png".to_owned(),
+            os: "Linux".to_owned(),
+            arch: "x86_64".to_owned(),
+            description: "Grates cheese.".to_owned(),
+            repo: "https://github.com/hackclub/say-cheese".to_owned(),
+        }
+    }
+
+    fn qr_problems(manifest: &Manifest) -> Vec<String> {
+        match manifest.validate() {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors
+                .into_iter()
+                .filter(|err| err.field == "qr.url")
+                .map(|err| err.problem)
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn accepts_http_links() {
+        for url in ["https://example.com/project", "http://example.com"] {
+            assert!(manifest(QrType::Url, url).validate().is_ok(), "{url}");
+        }
+    }
+
+    #[test]
+    fn refuses_links_a_browser_would_run() {
+        for url in [
+            "javascript:alert(1)",
+            "JavaScript:alert(1)",
+            "data:text/html,<script>alert(1)</script>",
+            "vbscript:msgbox(1)",
+        ] {
+            assert_eq!(qr_problems(&manifest(QrType::Url, url)).len(), 1, "{url}");
+        }
+    }
+
+    #[test]
+    fn accepts_anything_as_text() {
+        for payload in ["data:text/html;base64,PGgxPmhpPC9oMT4=", "just some words"] {
+            assert!(qr_problems(&manifest(QrType::Text, payload)).is_empty());
+        }
+    }
+
+    #[test]
+    fn refuses_empty_payloads() {
+        for ty in [QrType::Url, QrType::Text] {
+            assert_eq!(qr_problems(&manifest(ty, "")), ["must not be empty"]);
+        }
+    }
+
+    #[test]
+    fn maps_payload_kinds() {
+        assert_eq!(QrType::from(PayloadKind::Url), QrType::Url);
+        for kind in [PayloadKind::DataUri, PayloadKind::Text, PayloadKind::Binary] {
+            assert_eq!(QrType::from(kind), QrType::Text);
+        }
+    }
+}
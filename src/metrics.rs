@@ -0,0 +1,105 @@
+use std::{sync::LazyLock, time::Instant};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+};
+use prometheus::{
+    register_histogram_vec, register_int_counter_vec, register_int_gauge, register_int_gauge_vec,
+    HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, TextEncoder,
+};
+
+use crate::{error::AppError, mirror::Store, outbox::Outbox};
+
+// Airtable metrics are registered by the `airtable` module, and rendered along with these
+
+static HTTP_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "http_requests_total",
+        "Requests handled by the server",
+        &["method", "route", "status"]
+    )
+    .expect("http_requests_total should only be registered once")
+});
+
+static HTTP_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "http_request_duration_seconds",
+        "Time taken to handle a request",
+        &["method", "route"]
+    )
+    .expect("http_request_duration_seconds should only be registered once")
+});
+
+static EMAIL_DELIVERIES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "email_deliveries_total",
+        "Attempts to send a decision email, by outcome",
+        &["outcome"]
+    )
+    .expect("email_deliveries_total should only be registered once")
+});
+
+static QUEUE_DEPTH: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!("review_queue_depth", "Submissions waiting for a review")
+        .expect("review_queue_depth should only be registered once")
+});
+
+static OUTBOX_MESSAGES: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "outbox_messages",
+        "Decision emails in the outbox, by state",
+        &["state"]
+    )
+    .expect("outbox_messages should only be registered once")
+});
+
+/// Records the outcome of sending an email: `sent`, `retry` or `failed`.
+pub fn email_delivery(outcome: &str) {
+    EMAIL_DELIVERIES.with_label_values(&[outcome]).inc();
+}
+
+/// Middleware that counts and times every request by the route that handled it.
+///
+/// Routes are labeled with their pattern, like `/record/{id}`, so IDs don't end up in labels.
+pub async fn track(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let started = Instant::now();
+    let method = req.method().to_string();
+
+    let res = next.call(req).await?;
+
+    let route = res
+        .request()
+        .match_pattern()
+        .unwrap_or_else(|| "unmatched".to_owned());
+    HTTP_REQUESTS
+        .with_label_values(&[&method, &route, res.status().as_str()])
+        .inc();
+    HTTP_DURATION
+        .with_label_values(&[&method, &route])
+        .observe(started.elapsed().as_secs_f64());
+
+    Ok(res)
+}
+
+/// Renders every metric in the Prometheus text format, updating the gauges first.
+pub async fn render(store: &Store, outbox: &Outbox) -> Result<String, AppError> {
+    // a scrape shouldn't fail just because Airtable is unreachable
+    match store.queue_count().await {
+        Ok(count) => QUEUE_DEPTH.set(count as i64),
+        Err(err) => tracing::warn!("unable to count the queue for metrics: {err:?}"),
+    }
+
+    // every state is set, so one that drained doesn't keep reporting its last count
+    for (state, count) in outbox.counts()? {
+        OUTBOX_MESSAGES.with_label_values(&[state]).set(count);
+    }
+
+    Ok(TextEncoder::new()
+        .encode_to_string(&prometheus::gather())
+        .expect("metrics should encode as text"))
+}
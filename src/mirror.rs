@@ -0,0 +1,547 @@
+use std::{path::Path, sync::Mutex, time::Duration};
+
+use actix_web::web;
+use chrono::{DateTime, SecondsFormat, TimeDelta, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use saycheese_review::airtable::api::{self, ListRecords, Record, RecordId};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    audit::{self, Changes},
+    error::AppError,
+    records,
+    status::Status,
+    Submission, AIRTABLE_API_KEY, AIRTABLE_BASE_ID, FIELDS, SUBMISSION_TABLE, TABLE_VIEW,
+};
+
+/// How often the mirror asks Airtable for changed records.
+pub const SYNC_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How often the whole table is downloaded again. This is the only way to notice deleted records,
+/// and keeps attachment URLs from going stale.
+pub const FULL_SYNC_INTERVAL: TimeDelta = TimeDelta::hours(1);
+
+/// Airtable attachment URLs expire after a couple hours,
+/// so records mirrored longer ago than this are fetched again before being served.
+pub const MAX_RECORD_AGE: TimeDelta = TimeDelta::minutes(90);
+
+/// Incremental syncs look this far behind the last sync to allow for clock skew.
+const SYNC_OVERLAP: TimeDelta = TimeDelta::minutes(1);
+
+#[derive(Debug, Default, Serialize)]
+pub struct SyncReport {
+    pub full: bool,
+    /// Records that were added or changed.
+    pub updated: usize,
+    /// Records that are no longer in the view. Only found by a full sync.
+    pub removed: usize,
+}
+
+/// Local copy of the submission table in SQLite.
+pub struct Mirror {
+    conn: Mutex<Connection>,
+}
+
+fn record_from_row(row: &Row) -> rusqlite::Result<Record<Submission>> {
+    let conversion = |err: Box<dyn std::error::Error + Send + Sync>| {
+        rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, err)
+    };
+
+    let id: RecordId = row
+        .get::<_, String>("id")?
+        .parse()
+        .map_err(|err| conversion(Box::new(err)))?;
+    let fields: String = row.get("fields")?;
+    let fields = serde_json::from_str(&fields).map_err(|err| conversion(Box::new(err)))?;
+
+    Ok(Record::new(id, row.get("created_time")?, fields))
+}
+
+impl Mirror {
+    pub fn open(path: &Path) -> rusqlite::Result<Mirror> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS submissions (
+                id TEXT PRIMARY KEY,
+                created_time TEXT NOT NULL,
+                status TEXT NOT NULL,
+                fields TEXT NOT NULL,
+                synced_at TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS submissions_queue ON submissions (status, created_time);
+            CREATE TABLE IF NOT EXISTS sync_state (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            );",
+        )?;
+
+        Ok(Mirror {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn
+            .lock()
+            .expect("mirror connection should not be poisoned")
+    }
+
+    /// Gets a record if it was mirrored within [`MAX_RECORD_AGE`].
+    pub fn get(&self, id: &RecordId) -> rusqlite::Result<Option<Record<Submission>>> {
+        self.conn()
+            .query_row(
+                "SELECT * FROM submissions WHERE id = ?1 AND synced_at >= ?2",
+                params![id.to_string(), Utc::now() - MAX_RECORD_AGE],
+                record_from_row,
+            )
+            .optional()
+    }
+
+    /// Returns the ID of the oldest submission waiting for a review, other than those in `exclude`.
+    pub fn next_in_queue(&self, exclude: &[RecordId]) -> Result<Option<RecordId>, AppError> {
+        let id: Option<String> = self
+            .conn()
+            .query_row(
+                "SELECT id FROM submissions WHERE status IN (?1, ?2)
+                AND id NOT IN (SELECT value FROM json_each(?3))
+                ORDER BY created_time, id LIMIT 1",
+                params![
+                    Status::New.as_str(),
+                    Status::Resubmitted.as_str(),
+                    serde_json::to_string(exclude)?
+                ],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        id.map(|id| {
+            id.parse().map_err(|err| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    0,
+                    rusqlite::types::Type::Text,
+                    Box::new(err),
+                )
+            })
+        })
+        .transpose()
+        .map_err(AppError::from)
+    }
+
+    /// Every mirrored record, however long ago it was synced.
+    pub fn all(&self) -> rusqlite::Result<Vec<Record<Submission>>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare("SELECT * FROM submissions ORDER BY created_time, id")?;
+        let rows = stmt.query_map([], record_from_row)?;
+        rows.collect()
+    }
+
+    /// Counts the submissions waiting for a review.
+    pub fn queue_count(&self) -> rusqlite::Result<usize> {
+        self.conn().query_row(
+            "SELECT COUNT(*) FROM submissions WHERE status IN (?1, ?2)",
+            [Status::New.as_str(), Status::Resubmitted.as_str()],
+            |row| row.get(0),
+        )
+    }
+
+    /// Inserts or replaces records with what was just read from Airtable.
+    pub fn upsert(&self, records: &[Record<Submission>]) -> Result<(), AppError> {
+        self.write(records, false)?;
+        Ok(())
+    }
+
+    /// Removes records that were deleted from Airtable.
+    pub fn remove(&self, ids: &[RecordId]) -> rusqlite::Result<usize> {
+        let conn = self.conn();
+        let mut removed = 0;
+        for id in ids {
+            removed += conn.execute("DELETE FROM submissions WHERE id = ?1", [id.to_string()])?;
+        }
+        Ok(removed)
+    }
+
+    /// Writes records in a single transaction, first removing every other record if `replace` is set.
+    ///
+    /// Returns how many records were removed.
+    fn write(&self, records: &[Record<Submission>], replace: bool) -> Result<usize, AppError> {
+        let now = Utc::now();
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+
+        let mut removed = 0;
+        if replace {
+            tx.execute(
+                "CREATE TEMP TABLE IF NOT EXISTS synced_ids (id TEXT PRIMARY KEY)",
+                [],
+            )?;
+            tx.execute("DELETE FROM synced_ids", [])?;
+            for rec in records {
+                tx.execute(
+                    "INSERT OR IGNORE INTO synced_ids (id) VALUES (?1)",
+                    [rec.id().to_string()],
+                )?;
+            }
+            removed = tx.execute(
+                "DELETE FROM submissions WHERE id NOT IN (SELECT id FROM synced_ids)",
+                [],
+            )?;
+        }
+
+        for rec in records {
+            tx.execute(
+                "INSERT OR REPLACE INTO submissions (id, created_time, status, fields, synced_at)
+                VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    rec.id().to_string(),
+                    rec.created_time(),
+                    rec.fields().status.as_str(),
+                    serde_json::to_string(rec.fields())?,
+                    now
+                ],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(removed)
+    }
+
+    fn state(&self, key: &str) -> rusqlite::Result<Option<DateTime<Utc>>> {
+        self.conn()
+            .query_row(
+                "SELECT value FROM sync_state WHERE key = ?1",
+                [key],
+                |row| row.get(0),
+            )
+            .optional()
+    }
+
+    fn set_state(&self, key: &str, value: DateTime<Utc>) -> rusqlite::Result<()> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO sync_state (key, value) VALUES (?1, ?2)",
+            params![key, value],
+        )?;
+        Ok(())
+    }
+
+    /// Brings the mirror up to date with Airtable.
+    ///
+    /// Only records modified since the last sync are downloaded,
+    /// except every [`FULL_SYNC_INTERVAL`] when the whole table is downloaded again.
+    pub async fn sync(&self) -> Result<SyncReport, AppError> {
+        let started = Utc::now();
+        let last_sync = self.state("last_sync")?;
+        let last_full_sync = self.state("last_full_sync")?;
+
+        let full = match (last_sync, last_full_sync) {
+            (Some(_), Some(last_full_sync)) => started - last_full_sync > FULL_SYNC_INTERVAL,
+            _ => true,
+        };
+
+        let mut request =
+            ListRecords::new(AIRTABLE_BASE_ID.to_owned(), SUBMISSION_TABLE.to_owned())
+                .with_view(TABLE_VIEW.to_owned())
+                .with_fields(FIELDS.iter().map(ToString::to_string).collect());
+
+        if let (false, Some(last_sync)) = (full, last_sync) {
+            let since = (last_sync - SYNC_OVERLAP).to_rfc3339_opts(SecondsFormat::Secs, true);
+            request.filter_by_formula(format!(
+                "IS_AFTER(LAST_MODIFIED_TIME(), DATETIME_PARSE(\"{since}\"))"
+            ));
+        }
+
+        let records: Vec<Record<Submission>> = request.request(AIRTABLE_API_KEY).await?;
+        let mut report = SyncReport {
+            full,
+            updated: records.len(),
+            removed: 0,
+        };
+
+        if full {
+            report.removed = self.write(&records, true)?;
+            self.set_state("last_full_sync", started)?;
+        } else {
+            self.upsert(&records)?;
+        }
+        self.set_state("last_sync", started)?;
+
+        Ok(report)
+    }
+}
+
+/// Runs [`Mirror::sync`] every [`SYNC_INTERVAL`] for as long as the server is up.
+pub async fn sync_periodically(store: web::Data<Store>) {
+    let Some(mirror) = &store.mirror else {
+        return;
+    };
+
+    let mut interval = actix_web::rt::time::interval(SYNC_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        match mirror.sync().await {
+            Ok(report) if report.updated > 0 || report.removed > 0 => {
+                tracing::info!(
+                    "synced mirror: {} updated, {} removed",
+                    report.updated,
+                    report.removed
+                );
+            }
+            Ok(_) => {}
+            Err(err) => tracing::error!("failed to sync mirror: {err:?}"),
+        }
+    }
+}
+
+/// Matches the submissions that are waiting for a review.
+fn queue_formula() -> String {
+    format!(
+        "OR(status = \"{}\", status = \"{}\")",
+        Status::New,
+        Status::Resubmitted
+    )
+}
+
+/// Where submissions are read from and written to.
+///
+/// With a [`Mirror`], reads are served locally and writes go through
+/// to Airtable before updating the mirror. Without one every call goes to Airtable.
+pub struct Store {
+    mirror: Option<Mirror>,
+}
+
+impl Store {
+    pub fn new(mirror: Option<Mirror>) -> Store {
+        Store { mirror }
+    }
+
+    pub fn mirror(&self) -> Option<&Mirror> {
+        self.mirror.as_ref()
+    }
+
+    /// Gets a submission, falling back to Airtable if it hasn't been mirrored recently.
+    ///
+    /// The mirrored copy may be out of date, so use [`Store::fetch`] for anything that changes it.
+    pub async fn get(&self, id: &RecordId) -> Result<Record<Submission>, AppError> {
+        if let Some(mirror) = &self.mirror {
+            if let Some(rec) = mirror.get(id)? {
+                return Ok(rec);
+            }
+        }
+
+        self.fetch(id).await
+    }
+
+    /// Gets the current version of a submission from Airtable, updating the mirror with it.
+    pub async fn fetch(&self, id: &RecordId) -> Result<Record<Submission>, AppError> {
+        let rec = api::get_record(AIRTABLE_API_KEY, AIRTABLE_BASE_ID, SUBMISSION_TABLE, id).await?;
+
+        if let Some(mirror) = &self.mirror {
+            mirror.upsert(std::slice::from_ref(&rec))?;
+        }
+
+        Ok(rec)
+    }
+
+    /// Returns the next submission waiting for a review, other than those in `exclude`.
+    pub async fn next_in_queue(
+        &self,
+        exclude: &[RecordId],
+    ) -> Result<Option<Record<Submission>>, AppError> {
+        if let Some(mirror) = &self.mirror {
+            return match mirror.next_in_queue(exclude)? {
+                Some(id) => Ok(Some(self.get(&id).await?)),
+                None => Ok(None),
+            };
+        }
+
+        let mut conditions = vec![queue_formula()];
+        conditions.extend(exclude.iter().map(|id| {
+            format!(
+                "RECORD_ID() != {}",
+                records::formula_string(&id.to_string())
+            )
+        }));
+
+        let records: Vec<Record<Submission>> =
+            ListRecords::new(AIRTABLE_BASE_ID.to_owned(), SUBMISSION_TABLE.to_owned())
+                .with_view(TABLE_VIEW.to_owned())
+                .with_fields(FIELDS.iter().map(ToString::to_string).collect())
+                .with_filter_by_formula(format!("AND({})", conditions.join(", ")))
+                .with_max_records(1)
+                .with_cache(true)
+                .request(AIRTABLE_API_KEY)
+                .await?;
+
+        Ok(records.into_iter().next())
+    }
+
+    /// Every submission in the view.
+    ///
+    /// Attachment URLs may have expired when read from the mirror,
+    /// so this is only meant for looking at the other fields.
+    pub async fn all(&self) -> Result<Vec<Record<Submission>>, AppError> {
+        if let Some(mirror) = &self.mirror {
+            return Ok(mirror.all()?);
+        }
+
+        Ok(
+            ListRecords::new(AIRTABLE_BASE_ID.to_owned(), SUBMISSION_TABLE.to_owned())
+                .with_view(TABLE_VIEW.to_owned())
+                .with_fields(FIELDS.iter().map(ToString::to_string).collect())
+                .with_cache(true)
+                .request(AIRTABLE_API_KEY)
+                .await?,
+        )
+    }
+
+    /// Counts the submissions waiting for a review.
+    pub async fn queue_count(&self) -> Result<usize, AppError> {
+        if let Some(mirror) = &self.mirror {
+            return Ok(mirror.queue_count()?);
+        }
+
+        let records: Vec<Record<Value>> =
+            ListRecords::new(AIRTABLE_BASE_ID.to_owned(), SUBMISSION_TABLE.to_owned())
+                .with_view(TABLE_VIEW.to_owned())
+                .with_fields(vec!["status".to_owned()])
+                .with_filter_by_formula(queue_formula())
+                .with_cache(true)
+                .request(AIRTABLE_API_KEY)
+                .await?;
+
+        Ok(records.len())
+    }
+
+    /// Writes the changed fields of a submission to Airtable, then updates the mirror.
+    pub async fn update(
+        &self,
+        id: &RecordId,
+        changes: &Changes,
+    ) -> Result<Record<Submission>, AppError> {
+        let updated = api::update_record(
+            AIRTABLE_API_KEY,
+            AIRTABLE_BASE_ID,
+            SUBMISSION_TABLE,
+            id,
+            audit::patch(changes),
+            false,
+        )
+        .await?;
+
+        // Airtable responds with every field of the record, not just the changed ones
+        let (id, created_time) = (updated.id().clone(), updated.created_time());
+        let fields = serde_json::from_value(Value::Object(updated.into_fields()))?;
+        let updated = Record::new(id, created_time, fields);
+
+        if let Some(mirror) = &self.mirror {
+            mirror.upsert(std::slice::from_ref(&updated))?;
+        }
+
+        Ok(updated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn submission(id: &str, created: &str, status: Status) -> Record<Submission> {
+        let fields = serde_json::from_value(json!({
+            "project_name": "Pocket Tetris",
+            "Code URL": "https://github.com/example/project",
+            "Screenshot": [],
+            "Description": "",
+            "Optional - Override Hours Spent": 1.0,
+            "Email": "ada@example.com",
+            "qr_code": [],
+            "gallery_attribution": "Ada",
+            "os": "linux",
+            "architecture": "x86_64",
+            "status": status,
+        }))
+        .unwrap();
+
+        Record::new(id.parse().unwrap(), created.parse().unwrap(), fields)
+    }
+
+    fn ids(records: &[Record<Submission>]) -> Vec<String> {
+        records.iter().map(|rec| rec.id().to_string()).collect()
+    }
+
+    const A: &str = "recA1b2C3d4E5f6G7";
+    const B: &str = "recH8i9J0k1L2m3N4";
+    const C: &str = "recO5p6Q7r8S9t0U1";
+    const D: &str = "recV2w3X4y5Z6a7B8";
+
+    #[test]
+    fn next_in_queue_skips_claimed() {
+        let mirror = Mirror::open(Path::new(":memory:")).unwrap();
+        mirror
+            .upsert(&[
+                submission(A, "2026-10-01T12:00:00Z", Status::Accepted),
+                submission(B, "2026-10-02T12:00:00Z", Status::Resubmitted),
+                submission(C, "2026-10-02T12:00:00Z", Status::New),
+                submission(D, "2026-10-03T12:00:00Z", Status::New),
+            ])
+            .unwrap();
+
+        let next = |exclude: &[&str]| {
+            let exclude: Vec<RecordId> = exclude.iter().map(|id| id.parse().unwrap()).collect();
+            mirror
+                .next_in_queue(&exclude)
+                .unwrap()
+                .map(|id| id.to_string())
+        };
+
+        assert_eq!(next(&[]), Some(B.to_owned()));
+        assert_eq!(next(&[B]), Some(C.to_owned()));
+        assert_eq!(next(&[A, B, C]), Some(D.to_owned()));
+        assert_eq!(next(&[B, C, D]), None);
+        assert_eq!(mirror.queue_count().unwrap(), 3);
+    }
+
+    #[test]
+    fn replacing_removes_records_that_are_gone() {
+        let mirror = Mirror::open(Path::new(":memory:")).unwrap();
+        mirror
+            .upsert(&[
+                submission(A, "2026-10-01T12:00:00Z", Status::New),
+                submission(B, "2026-10-02T12:00:00Z", Status::New),
+                submission(C, "2026-10-03T12:00:00Z", Status::New),
+            ])
+            .unwrap();
+
+        let removed = mirror
+            .write(
+                &[
+                    submission(B, "2026-10-02T12:00:00Z", Status::Accepted),
+                    submission(D, "2026-10-04T12:00:00Z", Status::New),
+                ],
+                true,
+            )
+            .unwrap();
+
+        let all = mirror.all().unwrap();
+        assert_eq!(removed, 2);
+        assert_eq!(ids(&all), [B, D]);
+        assert_eq!(all[0].fields().status, Status::Accepted);
+    }
+
+    #[test]
+    fn upserting_keeps_other_records() {
+        let mirror = Mirror::open(Path::new(":memory:")).unwrap();
+        mirror
+            .upsert(&[submission(A, "2026-10-01T12:00:00Z", Status::New)])
+            .unwrap();
+        let removed = mirror
+            .write(&[submission(B, "2026-10-02T12:00:00Z", Status::New)], false)
+            .unwrap();
+
+        assert_eq!(removed, 0);
+        assert_eq!(ids(&mirror.all().unwrap()), [A, B]);
+    }
+}
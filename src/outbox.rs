@@ -0,0 +1,535 @@
+use std::{path::Path, sync::Mutex, time::Duration};
+
+use actix_web::web;
+use chrono::{DateTime, TimeDelta, Utc};
+use lettre::message::Mailbox;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use saycheese_review::airtable::api::{self, RecordId};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    audit::{self, Actor, AuditAction, AuditLog},
+    email::Email,
+    error::AppError,
+    mailer::{MailError, Mailer},
+    metrics, AIRTABLE_API_KEY, AIRTABLE_BASE_ID, SUBMISSION_TABLE,
+};
+
+/// How often the worker checks for messages that are due.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Number of delivery attempts before a message is marked as failed.
+pub const MAX_ATTEMPTS: u32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryState {
+    Pending,
+    Sent,
+    Failed,
+    /// The decision was undone before the email went out.
+    Cancelled,
+}
+
+impl DeliveryState {
+    const ALL: [DeliveryState; 4] = [
+        DeliveryState::Pending,
+        DeliveryState::Sent,
+        DeliveryState::Failed,
+        DeliveryState::Cancelled,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryState::Pending => "pending",
+            DeliveryState::Sent => "sent",
+            DeliveryState::Failed => "failed",
+            DeliveryState::Cancelled => "cancelled",
+        }
+    }
+
+    fn parse(state: &str) -> DeliveryState {
+        match state {
+            "sent" => DeliveryState::Sent,
+            "failed" => DeliveryState::Failed,
+            "cancelled" => DeliveryState::Cancelled,
+            _ => DeliveryState::Pending,
+        }
+    }
+}
+
+/// A decision email waiting in, or delivered from, the outbox.
+#[derive(Debug, Clone, Serialize)]
+pub struct OutboxMessage {
+    pub id: i64,
+    pub record_id: RecordId,
+    /// The review in the [`AuditLog`] that this email announces.
+    pub audit_id: Option<i64>,
+    pub to: String,
+    pub email: Email,
+    pub state: DeliveryState,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub next_attempt_at: DateTime<Utc>,
+    pub sent_at: Option<DateTime<Utc>>,
+}
+
+impl OutboxMessage {
+    fn from_row(row: &Row) -> rusqlite::Result<OutboxMessage> {
+        Ok(OutboxMessage {
+            id: row.get("id")?,
+            record_id: row.get::<_, String>("record_id")?.parse().map_err(|err| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    0,
+                    rusqlite::types::Type::Text,
+                    Box::new(err),
+                )
+            })?,
+            audit_id: row.get("audit_id")?,
+            to: row.get("recipient")?,
+            email: Email {
+                subject: row.get("subject")?,
+                html: row.get("html")?,
+                text: row.get("text")?,
+            },
+            state: DeliveryState::parse(&row.get::<_, String>("state")?),
+            attempts: row.get("attempts")?,
+            last_error: row.get("last_error")?,
+            created_at: row.get("created_at")?,
+            next_attempt_at: row.get("next_attempt_at")?,
+            sent_at: row.get("sent_at")?,
+        })
+    }
+}
+
+/// Persistent queue of decision emails.
+///
+/// `/review` only enqueues emails, a background worker does the actual sending
+/// so that an SMTP outage doesn't lose any decisions.
+pub struct Outbox {
+    conn: Mutex<Connection>,
+}
+
+impl Outbox {
+    pub fn open(path: &Path) -> rusqlite::Result<Outbox> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS outbox (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                record_id TEXT NOT NULL,
+                audit_id INTEGER,
+                recipient TEXT NOT NULL,
+                subject TEXT NOT NULL,
+                html TEXT NOT NULL,
+                text TEXT NOT NULL,
+                state TEXT NOT NULL DEFAULT 'pending',
+                attempts INTEGER NOT NULL DEFAULT 0,
+                last_error TEXT,
+                created_at TEXT NOT NULL,
+                next_attempt_at TEXT NOT NULL,
+                sent_at TEXT
+            );
+            CREATE INDEX IF NOT EXISTS outbox_due ON outbox (state, next_attempt_at);",
+        )?;
+
+        // outboxes created before emails were tied to their review
+        let has_audit_id: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('outbox') WHERE name = 'audit_id'",
+            [],
+            |row| row.get(0),
+        )?;
+        if !has_audit_id {
+            conn.execute("ALTER TABLE outbox ADD COLUMN audit_id INTEGER", [])?;
+        }
+
+        Ok(Outbox {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn
+            .lock()
+            .expect("outbox connection should not be poisoned")
+    }
+
+    /// Queues the email announcing the review recorded as `audit_id`.
+    ///
+    /// `to` is only parsed when the email is sent, so a malformed address
+    /// leaves the message failed instead of losing the decision.
+    pub fn enqueue(
+        &self,
+        record_id: &RecordId,
+        audit_id: i64,
+        to: &str,
+        email: &Email,
+    ) -> rusqlite::Result<i64> {
+        let now = Utc::now();
+        let conn = self.conn();
+        conn.execute(
+            "INSERT INTO outbox (record_id, audit_id, recipient, subject, html, text, created_at, next_attempt_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)",
+            params![
+                record_id.to_string(),
+                audit_id,
+                to,
+                email.subject,
+                email.html,
+                email.text,
+                now
+            ],
+        )?;
+
+        Ok(conn.last_insert_rowid())
+    }
+
+    pub fn get(&self, id: i64) -> rusqlite::Result<Option<OutboxMessage>> {
+        self.conn()
+            .query_row(
+                "SELECT * FROM outbox WHERE id = ?1",
+                [id],
+                OutboxMessage::from_row,
+            )
+            .optional()
+    }
+
+    /// Counts messages in every state, including states that have none.
+    pub fn counts(&self) -> rusqlite::Result<Vec<(&'static str, i64)>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare("SELECT COUNT(*) FROM outbox WHERE state = ?1")?;
+
+        DeliveryState::ALL
+            .iter()
+            .map(|state| {
+                let count = stmt.query_row([state.as_str()], |row| row.get(0))?;
+                Ok((state.as_str(), count))
+            })
+            .collect()
+    }
+
+    /// Lists messages, newest first, optionally only those in `state`.
+    pub fn list(&self, state: Option<DeliveryState>) -> rusqlite::Result<Vec<OutboxMessage>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT * FROM outbox WHERE ?1 IS NULL OR state = ?1 ORDER BY created_at DESC",
+        )?;
+        let rows = stmt.query_map([state.map(|state| state.as_str())], OutboxMessage::from_row)?;
+        rows.collect()
+    }
+
+    fn due(&self, now: DateTime<Utc>) -> rusqlite::Result<Vec<OutboxMessage>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT * FROM outbox WHERE state = 'pending' AND next_attempt_at <= ?1 ORDER BY id",
+        )?;
+        let rows = stmt.query_map([now], OutboxMessage::from_row)?;
+        rows.collect()
+    }
+
+    /// Returns `false` if the message was cancelled while it was being sent.
+    fn mark_sent(&self, id: i64, now: DateTime<Utc>) -> rusqlite::Result<bool> {
+        let changed = self.conn().execute(
+            "UPDATE outbox SET state = 'sent', attempts = attempts + 1, last_error = NULL, sent_at = ?2
+            WHERE id = ?1 AND state = 'pending'",
+            params![id, now],
+        )?;
+        Ok(changed > 0)
+    }
+
+    fn mark_attempt_failed(
+        &self,
+        message: &OutboxMessage,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> rusqlite::Result<()> {
+        let state = match retry_at {
+            Some(_) => DeliveryState::Pending,
+            None => DeliveryState::Failed,
+        };
+
+        self.conn().execute(
+            "UPDATE outbox SET state = ?2, attempts = attempts + 1, last_error = ?3,
+                next_attempt_at = COALESCE(?4, next_attempt_at)
+            WHERE id = ?1",
+            params![message.id, state.as_str(), error, retry_at],
+        )?;
+        Ok(())
+    }
+
+    /// Cancels the pending messages for the review recorded as `audit_id`.
+    ///
+    /// Returns the number of messages cancelled.
+    pub fn cancel_pending(&self, audit_id: i64) -> rusqlite::Result<usize> {
+        self.conn().execute(
+            "UPDATE outbox SET state = 'cancelled' WHERE audit_id = ?1 AND state = 'pending'",
+            [audit_id],
+        )
+    }
+
+    /// Counts the messages for the review recorded as `audit_id` that have already been sent.
+    pub fn sent_for(&self, audit_id: i64) -> rusqlite::Result<usize> {
+        self.conn().query_row(
+            "SELECT COUNT(*) FROM outbox WHERE audit_id = ?1 AND state = 'sent'",
+            [audit_id],
+            |row| row.get(0),
+        )
+    }
+
+    /// Puts a message back in the queue to be sent as soon as possible.
+    ///
+    /// Returns `false` if there is no such message or it isn't pending or failed,
+    /// so emails that were sent or cancelled by an undo stay that way.
+    pub fn resend(&self, id: i64) -> rusqlite::Result<bool> {
+        let changed = self.conn().execute(
+            "UPDATE outbox SET state = 'pending', attempts = 0, next_attempt_at = ?2
+            WHERE id = ?1 AND state IN ('pending', 'failed')",
+            params![id, Utc::now()],
+        )?;
+        Ok(changed > 0)
+    }
+}
+
+/// Exponential backoff between attempts, starting at one minute.
+fn backoff(attempts: u32) -> TimeDelta {
+    TimeDelta::minutes(1 << attempts.min(10))
+}
+
+/// Delivery fields on a submission, kept separate from
+/// [`Submission`](crate::Submission) so that only these are written back.
+#[derive(Debug, Serialize, Deserialize)]
+struct Delivery {
+    #[serde(default)]
+    email_sent_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    email_error: Option<String>,
+}
+
+async fn record_delivery(audit: &AuditLog, record_id: &RecordId, delivery: Delivery) {
+    let changes = audit::diff::<Delivery, _>(None, &delivery);
+    let res = api::update_record(
+        AIRTABLE_API_KEY,
+        AIRTABLE_BASE_ID,
+        SUBMISSION_TABLE,
+        record_id,
+        delivery,
+        false,
+    )
+    .await;
+
+    if let Err(err) = res {
+        tracing::error!("unable to record email delivery on {record_id}: {err:?}");
+        return;
+    }
+
+    let res = changes.map_err(AppError::from).and_then(|changes| {
+        audit.record(
+            &Actor::system(),
+            record_id,
+            AuditAction::EmailDelivery,
+            &changes,
+        )
+    });
+
+    if let Err(err) = res {
+        tracing::error!("unable to audit email delivery on {record_id}: {err:?}");
+    }
+}
+
+async fn deliver(mailer: &Mailer, message: &OutboxMessage) -> Result<(), MailError> {
+    let to: Mailbox = message.to.parse()?;
+    mailer.send(to, &message.email).await
+}
+
+/// Sends every due message once, retrying failures later with [`backoff`].
+pub async fn deliver_due(
+    outbox: &Outbox,
+    mailer: &Mailer,
+    audit: &AuditLog,
+) -> rusqlite::Result<()> {
+    for message in outbox.due(Utc::now())? {
+        match deliver(mailer, &message).await {
+            Ok(()) => {
+                let now = Utc::now();
+                if outbox.mark_sent(message.id, now)? {
+                    tracing::info!("sent email {} for {}", message.id, message.record_id);
+                } else {
+                    tracing::warn!(
+                        "sent email {} for {}, but it was cancelled while sending",
+                        message.id,
+                        message.record_id
+                    );
+                }
+                metrics::email_delivery("sent");
+
+                let delivery = Delivery {
+                    email_sent_at: Some(now),
+                    email_error: None,
+                };
+                record_delivery(audit, &message.record_id, delivery).await;
+            }
+            Err(err) => {
+                let error = format!("{err}: {err:?}");
+                let attempts = message.attempts + 1;
+                // a malformed address isn't going to fix itself
+                let retry_at = match err {
+                    MailError::Address(_) => None,
+                    _ if attempts >= MAX_ATTEMPTS => None,
+                    _ => Some(Utc::now() + backoff(attempts)),
+                };
+
+                outbox.mark_attempt_failed(&message, &error, retry_at)?;
+                metrics::email_delivery(match retry_at {
+                    Some(_) => "retry",
+                    None => "failed",
+                });
+                tracing::warn!(
+                    "attempt {attempts} to send email {} for {} failed: {error}",
+                    message.id,
+                    message.record_id
+                );
+
+                let delivery = Delivery {
+                    email_sent_at: None,
+                    email_error: Some(error),
+                };
+                record_delivery(audit, &message.record_id, delivery).await;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs [`deliver_due`] every [`POLL_INTERVAL`] for as long as the server is up.
+pub async fn deliver_periodically(
+    outbox: web::Data<Outbox>,
+    mailer: Mailer,
+    audit: web::Data<AuditLog>,
+) {
+    let mut interval = actix_web::rt::time::interval(POLL_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(err) = deliver_due(&outbox, &mailer, &audit).await {
+            tracing::error!("failed to deliver queued emails: {err:?}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_every_state() {
+        let outbox = Outbox::open(Path::new(":memory:")).unwrap();
+        assert_eq!(
+            outbox.counts().unwrap(),
+            [("pending", 0), ("sent", 0), ("failed", 0), ("cancelled", 0)]
+        );
+    }
+
+    fn email() -> Email {
+        Email {
+            subject: "Say Cheese: an update on Pocket Tetris".to_owned(),
+            html: "<p>Hi</p>".to_owned(),
+            text: "Hi".to_owned(),
+        }
+    }
+
+    #[test]
+    fn cancels_by_review() {
+        let outbox = Outbox::open(Path::new(":memory:")).unwrap();
+        let id: RecordId = "recA1b2C3d4E5f6G7".parse().unwrap();
+
+        let earlier = outbox.enqueue(&id, 1, "ada@example.com", &email()).unwrap();
+        let decision = outbox.enqueue(&id, 2, "ada@example.com", &email()).unwrap();
+        assert!(outbox.mark_sent(earlier, Utc::now()).unwrap());
+
+        assert_eq!(outbox.sent_for(2).unwrap(), 0);
+        assert_eq!(outbox.cancel_pending(2).unwrap(), 1);
+        assert_eq!(
+            outbox.get(decision).unwrap().unwrap().state,
+            DeliveryState::Cancelled
+        );
+        assert_eq!(outbox.cancel_pending(1).unwrap(), 0);
+        assert_eq!(outbox.sent_for(1).unwrap(), 1);
+    }
+
+    #[test]
+    fn keeps_cancelled_emails_cancelled() {
+        let outbox = Outbox::open(Path::new(":memory:")).unwrap();
+        let id: RecordId = "recA1b2C3d4E5f6G7".parse().unwrap();
+        let message = outbox.enqueue(&id, 1, "ada@example.com", &email()).unwrap();
+
+        outbox.cancel_pending(1).unwrap();
+        assert!(!outbox.mark_sent(message, Utc::now()).unwrap());
+        assert!(!outbox.resend(message).unwrap());
+        assert_eq!(
+            outbox.get(message).unwrap().unwrap().state,
+            DeliveryState::Cancelled
+        );
+    }
+
+    #[test]
+    fn resends_only_pending_and_failed() {
+        let outbox = Outbox::open(Path::new(":memory:")).unwrap();
+        let id: RecordId = "recA1b2C3d4E5f6G7".parse().unwrap();
+        let sent = outbox.enqueue(&id, 1, "ada@example.com", &email()).unwrap();
+        let failed = outbox.enqueue(&id, 2, "not an address", &email()).unwrap();
+
+        outbox.mark_sent(sent, Utc::now()).unwrap();
+        let message = outbox.get(failed).unwrap().unwrap();
+        outbox
+            .mark_attempt_failed(&message, "invalid address", None)
+            .unwrap();
+
+        assert!(!outbox.resend(sent).unwrap());
+        assert!(outbox.resend(failed).unwrap());
+        assert!(outbox.resend(failed).unwrap());
+        assert!(!outbox.resend(404).unwrap());
+        assert_eq!(
+            outbox.get(failed).unwrap().unwrap().state,
+            DeliveryState::Pending
+        );
+    }
+
+    #[test]
+    fn adds_audit_id_to_old_outboxes() {
+        let dir = std::env::temp_dir().join(format!("outbox-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("old.db");
+        let _ = std::fs::remove_file(&path);
+
+        Connection::open(&path)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE outbox (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    record_id TEXT NOT NULL,
+                    recipient TEXT NOT NULL,
+                    subject TEXT NOT NULL,
+                    html TEXT NOT NULL,
+                    text TEXT NOT NULL,
+                    state TEXT NOT NULL DEFAULT 'pending',
+                    attempts INTEGER NOT NULL DEFAULT 0,
+                    last_error TEXT,
+                    created_at TEXT NOT NULL,
+                    next_attempt_at TEXT NOT NULL,
+                    sent_at TEXT
+                );",
+            )
+            .unwrap();
+
+        let outbox = Outbox::open(&path).unwrap();
+        let id: RecordId = "recA1b2C3d4E5f6G7".parse().unwrap();
+        let message = outbox.enqueue(&id, 7, "ada@example.com", &email()).unwrap();
+        assert_eq!(outbox.get(message).unwrap().unwrap().audit_id, Some(7));
+
+        drop(outbox);
+        Outbox::open(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,138 @@
+use actix_web::web;
+use base64::Engine;
+use saycheese_review::airtable::Attachment;
+use serde::Serialize;
+
+use crate::error::AppError;
+
+/// What a decoded QR payload looks like.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PayloadKind {
+    /// An `http` or `https` URL.
+    Url,
+    /// A `data:` URI, usually holding the whole project.
+    DataUri,
+    /// Any other UTF-8 text.
+    Text,
+    /// Bytes that aren't valid UTF-8, returned base64 encoded.
+    Binary,
+}
+
+impl PayloadKind {
+    pub fn classify(payload: &str) -> PayloadKind {
+        let lower = payload.trim_start().to_ascii_lowercase();
+
+        if lower.starts_with("data:") {
+            PayloadKind::DataUri
+        } else if lower.starts_with("http://") || lower.starts_with("https://") {
+            PayloadKind::Url
+        } else {
+            PayloadKind::Text
+        }
+    }
+}
+
+/// A single QR code found in an image.
+#[derive(Debug, Clone, Serialize)]
+pub struct DecodedQr {
+    /// The decoded payload. Base64 encoded if `kind` is [`PayloadKind::Binary`].
+    pub payload: String,
+    pub kind: PayloadKind,
+    /// QR version between 1 and 40.
+    pub version: usize,
+    pub ecc_level: EccLevel,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum EccLevel {
+    L,
+    M,
+    Q,
+    H,
+}
+
+impl EccLevel {
+    /// Converts the raw format bits read from the code.
+    fn from_format_bits(bits: u16) -> EccLevel {
+        match bits {
+            0 => EccLevel::M,
+            1 => EccLevel::L,
+            2 => EccLevel::H,
+            _ => EccLevel::Q,
+        }
+    }
+}
+
+/// Everything that could be read from a QR code image.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct QrScan {
+    pub codes: Vec<DecodedQr>,
+    /// Problems with the image or with individual codes in it.
+    pub errors: Vec<String>,
+}
+
+/// Finds and decodes every QR code in an encoded image.
+pub fn scan(image: &[u8]) -> QrScan {
+    let mut result = QrScan::default();
+
+    let image = match image::load_from_memory(image) {
+        Ok(image) => image.to_luma8(),
+        Err(err) => {
+            result.errors.push(format!("unable to read image: {err}"));
+            return result;
+        }
+    };
+
+    let mut prepared = rqrr::PreparedImage::prepare(image);
+    let grids = prepared.detect_grids();
+
+    if grids.is_empty() {
+        result.errors.push("no QR code found in image".to_owned());
+    }
+
+    for grid in grids {
+        let mut bytes = Vec::new();
+        let meta = match grid.decode_to(&mut bytes) {
+            Ok(meta) => meta,
+            Err(err) => {
+                result
+                    .errors
+                    .push(format!("unable to decode QR code: {err}"));
+                continue;
+            }
+        };
+
+        let (payload, kind) = match String::from_utf8(bytes) {
+            Ok(text) => {
+                let kind = PayloadKind::classify(&text);
+                (text, kind)
+            }
+            Err(err) => (
+                base64::prelude::BASE64_STANDARD.encode(err.into_bytes()),
+                PayloadKind::Binary,
+            ),
+        };
+
+        result.codes.push(DecodedQr {
+            payload,
+            kind,
+            version: meta.version.0,
+            ecc_level: EccLevel::from_format_bits(meta.ecc_level),
+        });
+    }
+
+    result
+}
+
+/// Downloads an image attachment from Airtable and scans it.
+pub async fn scan_attachment(attachment: &Attachment) -> Result<QrScan, AppError> {
+    let image = attachment.download().await?;
+
+    // decoding is CPU bound, keep it off the async workers
+    let scan = web::block(move || scan(&image))
+        .await
+        .map_err(std::io::Error::other)?;
+
+    Ok(scan)
+}
@@ -0,0 +1,216 @@
+use chrono::{DateTime, Utc};
+use saycheese_review::airtable::api::{Direction, ListRecords, Page, Record, RecordId};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::AppError, status::Status, Submission, AIRTABLE_API_KEY, AIRTABLE_BASE_ID, FIELDS,
+    SUBMISSION_TABLE, TABLE_VIEW,
+};
+
+pub const DEFAULT_PAGE_SIZE: usize = 25;
+/// Airtable won't return more than this many records per request.
+pub const MAX_PAGE_SIZE: usize = 100;
+
+/// Fields that the listing can be sorted by.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    Name,
+    Author,
+    Os,
+    Architecture,
+    Status,
+}
+
+impl SortField {
+    fn field(&self) -> &'static str {
+        match self {
+            SortField::Name => "project_name",
+            SortField::Author => "gallery_attribution",
+            SortField::Os => "os",
+            SortField::Architecture => "architecture",
+            SortField::Status => "status",
+        }
+    }
+}
+
+/// Filters for the review queue. Every filter that is set has to match.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecordQuery {
+    pub status: Option<Status>,
+    pub os: Option<String>,
+    pub architecture: Option<String>,
+    /// Case-insensitive search through the name, author, email and description.
+    pub q: Option<String>,
+    /// Sort by this field instead of the view's order.
+    pub sort: Option<SortField>,
+    #[serde(default = "ascending")]
+    pub order: Direction,
+    pub page_size: Option<usize>,
+    /// The `next` cursor of the previous page. The other parameters must not change between pages.
+    pub cursor: Option<String>,
+}
+
+fn ascending() -> Direction {
+    Direction::Ascending
+}
+
+/// Just enough of a submission to show it in a list.
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordSummary {
+    pub id: RecordId,
+    pub created_time: DateTime<Utc>,
+    pub name: String,
+    pub author: String,
+    pub os: String,
+    pub architecture: String,
+    pub status: Status,
+    pub resubmit_deadline: Option<DateTime<Utc>>,
+    /// Small thumbnail of the screenshot.
+    pub thumbnail: Option<String>,
+}
+
+impl From<Record<Submission>> for RecordSummary {
+    fn from(rec: Record<Submission>) -> Self {
+        let id = rec.id().clone();
+        let created_time = rec.created_time();
+        let submission = rec.into_fields();
+
+        RecordSummary {
+            id,
+            created_time,
+            thumbnail: submission
+                .screenshot
+                .first()
+                .map(|screenshot| screenshot.thumbnails().small().url().to_owned()),
+            name: submission.name,
+            author: submission.gallery_attribution,
+            os: submission.os,
+            architecture: submission.architecture,
+            status: submission.status,
+            resubmit_deadline: submission.resubmit_deadline,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecordsPage {
+    pub records: Vec<RecordSummary>,
+    /// Pass as `cursor` to get the next page, `None` on the last page.
+    pub next: Option<String>,
+}
+
+/// Quotes a string for use in an Airtable formula.
+pub fn formula_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl RecordQuery {
+    fn formula(&self) -> Option<String> {
+        let mut conditions = Vec::new();
+
+        if let Some(status) = self.status {
+            conditions.push(format!("status = {}", formula_string(status.as_str())));
+        }
+
+        if let Some(os) = &self.os {
+            conditions.push(format!("os = {}", formula_string(os)));
+        }
+
+        if let Some(architecture) = &self.architecture {
+            conditions.push(format!("architecture = {}", formula_string(architecture)));
+        }
+
+        if let Some(q) = self.q.as_deref().map(str::trim).filter(|q| !q.is_empty()) {
+            let needle = formula_string(&q.to_lowercase());
+            let fields = [
+                "project_name",
+                "gallery_attribution",
+                "Email",
+                "Description",
+            ]
+            .map(|field| format!("SEARCH({needle}, LOWER({{{field}}}))"));
+            conditions.push(format!("OR({})", fields.join(", ")));
+        }
+
+        match conditions.len() {
+            0 => None,
+            1 => conditions.pop(),
+            _ => Some(format!("AND({})", conditions.join(", "))),
+        }
+    }
+}
+
+/// Lists one page of submissions matching `query`.
+pub async fn list(query: RecordQuery) -> Result<RecordsPage, AppError> {
+    let page_size = query.page_size.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&page_size) {
+        return Err(AppError::BadRequest(format!(
+            "`page_size` must be between 1 and {MAX_PAGE_SIZE}"
+        )));
+    }
+
+    let mut request = ListRecords::new(AIRTABLE_BASE_ID.to_owned(), SUBMISSION_TABLE.to_owned())
+        .with_view(TABLE_VIEW.to_owned())
+        .with_fields(FIELDS.iter().map(ToString::to_string).collect())
+        .with_page_size(page_size)
+        .with_cache(true);
+
+    if let Some(formula) = query.formula() {
+        request.filter_by_formula(formula);
+    }
+
+    if let Some(sort) = query.sort {
+        request.sort(sort.field().to_owned(), query.order);
+    }
+
+    if let Some(cursor) = query.cursor {
+        request.offset(cursor);
+    }
+
+    let page: Page<Submission> = request.request_page(AIRTABLE_API_KEY).await?;
+
+    Ok(RecordsPage {
+        records: page.records.into_iter().map(RecordSummary::from).collect(),
+        next: page.offset,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn quotes_formula_strings() {
+        for (value, quoted) in [
+            ("", r#""""#),
+            ("linux", r#""linux""#),
+            (r#"say "cheese""#, r#""say \"cheese\"""#),
+            (r"C:\games", r#""C:\\games""#),
+            (r#"\""#, r#""\\\"""#),
+            (r#"") & TRUE() & (""#, r#""\") & TRUE() & (\"""#),
+            ("it's", r#""it's""#),
+        ] {
+            assert_eq!(formula_string(value), quoted, "{value:?}");
+        }
+    }
+
+    #[test]
+    fn quotes_filters_in_formula() {
+        let query: RecordQuery =
+            serde_json::from_value(json!({ "os": r#"linux", "x"#, "q": r"  A\B " })).unwrap();
+
+        assert_eq!(
+            query.formula().unwrap(),
+            concat!(
+                r#"AND(os = "linux\", \"x", OR("#,
+                r#"SEARCH("a\\b", LOWER({project_name})), "#,
+                r#"SEARCH("a\\b", LOWER({gallery_attribution})), "#,
+                r#"SEARCH("a\\b", LOWER({Email})), "#,
+                r#"SEARCH("a\\b", LOWER({Description}))))"#,
+            )
+        );
+    }
+}
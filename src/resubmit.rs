@@ -0,0 +1,423 @@
+use std::time::Duration;
+
+use actix_web::web;
+use chrono::{DateTime, TimeDelta, Utc};
+use saycheese_review::airtable::api::{self, ApiError, ListRecords, Record, RecordId};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    audit::{self, Actor, AuditAction, AuditLog},
+    error::AppError,
+    status::Status,
+    Submission, AIRTABLE_API_KEY, AIRTABLE_BASE_ID, FIELDS, SUBMISSION_TABLE, TABLE_VIEW,
+};
+
+/// How long a submitter has to resubmit after being rejected.
+/// This has to match the "three days" promised in the rejection email.
+pub const RESUBMIT_WINDOW: TimeDelta = TimeDelta::days(3);
+
+/// How often rejected submissions are checked for resubmissions and expired windows.
+pub const SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+pub fn deadline_from(decided: DateTime<Utc>) -> DateTime<Utc> {
+    decided + RESUBMIT_WINDOW
+}
+
+/// Lists every rejected submission that can still be resubmitted.
+pub async fn open_windows() -> Result<Vec<Record<Submission>>, ApiError> {
+    ListRecords::new(AIRTABLE_BASE_ID.to_owned(), SUBMISSION_TABLE.to_owned())
+        .with_view(TABLE_VIEW.to_owned())
+        .with_fields(FIELDS.iter().map(ToString::to_string).collect())
+        .with_filter_by_formula(format!(
+            "AND(status = \"{}\", IS_AFTER(resubmit_deadline, NOW()))",
+            Status::Rejected
+        ))
+        .request(AIRTABLE_API_KEY)
+        .await
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct SweepReport {
+    /// Pairs of `(rejected, resubmission)` records that were linked up.
+    pub resubmitted: Vec<(RecordId, RecordId)>,
+    /// Rejected records whose window expired and were closed.
+    pub expired: Vec<RecordId>,
+}
+
+/// Detects resubmissions of rejected projects and closes expired windows.
+///
+/// A resubmission is a new record with the same email and project name as a
+/// rejected record, created between the rejection and the rejected record's deadline.
+/// The new record is moved to [`Status::Resubmitted`] so it gets reviewed again,
+/// and the rejected record is moved to [`Status::RejectedFinal`] with its deadline cleared,
+/// since its window has been used.
+/// Rejected records still holding a deadline in the past are moved to
+/// [`Status::RejectedFinal`] as well.
+pub async fn sweep(audit: &AuditLog, actor: &Actor) -> Result<SweepReport, AppError> {
+    let rejected: Vec<Record<Submission>> =
+        ListRecords::new(AIRTABLE_BASE_ID.to_owned(), SUBMISSION_TABLE.to_owned())
+            .with_view(TABLE_VIEW.to_owned())
+            .with_fields(FIELDS.iter().map(ToString::to_string).collect())
+            .with_filter_by_formula(format!(
+                "AND(status = \"{}\", resubmit_deadline)",
+                Status::Rejected
+            ))
+            .request(AIRTABLE_API_KEY)
+            .await?;
+
+    if rejected.is_empty() {
+        return Ok(SweepReport::default());
+    }
+
+    let fresh: Vec<Record<Submission>> =
+        ListRecords::new(AIRTABLE_BASE_ID.to_owned(), SUBMISSION_TABLE.to_owned())
+            .with_view(TABLE_VIEW.to_owned())
+            .with_fields(FIELDS.iter().map(ToString::to_string).collect())
+            .with_filter_by_formula(format!("status = \"{}\"", Status::New))
+            .request(AIRTABLE_API_KEY)
+            .await?;
+
+    let mut report = SweepReport::default();
+
+    for (old, outcome) in plan(&rejected, &fresh, Utc::now()) {
+        match outcome {
+            Outcome::Resubmitted(new) => {
+                update(audit, actor, new, AuditAction::Resubmission, |data| {
+                    data.status = data.status.transition(Status::Resubmitted)?;
+                    Ok(())
+                })
+                .await?;
+                update(audit, actor, old, AuditAction::Resubmission, |data| {
+                    data.status = data.status.transition(Status::RejectedFinal)?;
+                    data.resubmit_deadline = None;
+                    Ok(())
+                })
+                .await?;
+
+                tracing::info!("{old} was resubmitted as {new}");
+                report.resubmitted.push((old.clone(), new.clone()));
+            }
+            Outcome::Expired => {
+                update(audit, actor, old, AuditAction::Expire, |data| {
+                    data.status = data.status.transition(Status::RejectedFinal)?;
+                    Ok(())
+                })
+                .await?;
+
+                tracing::info!("resubmission window for {old} expired");
+                report.expired.push(old.clone());
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// What [`sweep`] does with a rejected record.
+#[derive(Debug, PartialEq)]
+enum Outcome<'a> {
+    /// This new record is its resubmission.
+    Resubmitted(&'a RecordId),
+    Expired,
+}
+
+/// Pairs rejected records with their resubmissions among `fresh`, or expires them
+/// if their deadline passed before `now` without one.
+///
+/// Each fresh record resubmits at most one rejected record. Rejected records that are
+/// still waiting, or have no deadline, are left out.
+fn plan<'a>(
+    rejected: &'a [Record<Submission>],
+    fresh: &'a [Record<Submission>],
+    now: DateTime<Utc>,
+) -> Vec<(&'a RecordId, Outcome<'a>)> {
+    let mut outcomes: Vec<(&RecordId, Outcome)> = Vec::new();
+
+    for old in rejected {
+        let Some(deadline) = old.fields().resubmit_deadline else {
+            continue;
+        };
+
+        // the deadline is set when the record is rejected
+        let rejected_at = deadline - RESUBMIT_WINDOW;
+
+        let resubmission = fresh.iter().find(|new| {
+            new.created_time() > rejected_at
+                && new.created_time() <= deadline
+                && same_project(new.fields(), old.fields())
+                && !outcomes
+                    .iter()
+                    .any(|(_, outcome)| *outcome == Outcome::Resubmitted(new.id()))
+        });
+
+        if let Some(new) = resubmission {
+            outcomes.push((old.id(), Outcome::Resubmitted(new.id())));
+        } else if deadline <= now {
+            outcomes.push((old.id(), Outcome::Expired));
+        }
+    }
+
+    outcomes
+}
+
+/// Runs [`sweep`] every [`SWEEP_INTERVAL`] for as long as the server is up.
+pub async fn sweep_periodically(audit: web::Data<AuditLog>) {
+    let mut interval = actix_web::rt::time::interval(SWEEP_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(err) = sweep(&audit, &Actor::system()).await {
+            tracing::error!("failed to sweep resubmission windows: {err:?}");
+        }
+    }
+}
+
+fn same_project(a: &Submission, b: &Submission) -> bool {
+    a.email.trim().eq_ignore_ascii_case(b.email.trim())
+        && a.name.trim().eq_ignore_ascii_case(b.name.trim())
+}
+
+/// Applies `change` to the current version of a record in Airtable,
+/// writing back only the fields it changed.
+///
+/// The listing the sweep works from may be out of date by the time a record is reached,
+/// so the record is fetched again before the change is checked and made.
+async fn update<F>(
+    audit: &AuditLog,
+    actor: &Actor,
+    id: &RecordId,
+    action: AuditAction,
+    change: F,
+) -> Result<(), AppError>
+where
+    F: FnOnce(&mut Submission) -> Result<(), AppError>,
+{
+    let rec: Record<Submission> =
+        api::get_record(AIRTABLE_API_KEY, AIRTABLE_BASE_ID, SUBMISSION_TABLE, id).await?;
+    let mut data = rec.fields().clone();
+    change(&mut data)?;
+
+    let changes = audit::diff(Some(rec.fields()), &data)?;
+    let _: Record<serde_json::Map<String, Value>> = api::update_record(
+        AIRTABLE_API_KEY,
+        AIRTABLE_BASE_ID,
+        SUBMISSION_TABLE,
+        id,
+        audit::patch(&changes),
+        false,
+    )
+    .await?;
+    audit.record(actor, id, action, &changes)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    fn submission(
+        id: &str,
+        created: &str,
+        email: &str,
+        name: &str,
+        deadline: Option<&str>,
+    ) -> Record<Submission> {
+        let fields = serde_json::from_value(json!({
+            "project_name": name,
+            "Code URL": "https://github.com/example/project",
+            "Screenshot": [],
+            "Description": "",
+            "Optional - Override Hours Spent": 1.0,
+            "Email": email,
+            "qr_code": [],
+            "gallery_attribution": "",
+            "os": "linux",
+            "architecture": "x86_64",
+            "status": if deadline.is_some() { "rejected" } else { "new" },
+            "resubmit_deadline": deadline,
+        }))
+        .unwrap();
+
+        Record::new(id.parse().unwrap(), at(created), fields)
+    }
+
+    const OLD: &str = "recOld00000000001";
+    const NEW: &str = "recNew00000000001";
+
+    fn rejected(deadline: &str) -> Record<Submission> {
+        submission(
+            OLD,
+            "2026-10-01T12:00:00Z",
+            "ada@example.com",
+            "Pocket Tetris",
+            Some(deadline),
+        )
+    }
+
+    fn outcome(
+        rejected: &[Record<Submission>],
+        fresh: &[Record<Submission>],
+        now: &str,
+    ) -> Vec<(String, Option<String>)> {
+        plan(rejected, fresh, at(now))
+            .into_iter()
+            .map(|(old, outcome)| {
+                let new = match outcome {
+                    Outcome::Resubmitted(new) => Some(new.to_string()),
+                    Outcome::Expired => None,
+                };
+                (old.to_string(), new)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_by_email_and_name() {
+        let rejected = [rejected("2026-10-05T12:00:00Z")];
+        let fresh = [submission(
+            NEW,
+            "2026-10-03T08:00:00Z",
+            " Ada@Example.com ",
+            "pocket tetris",
+            None,
+        )];
+
+        assert_eq!(
+            outcome(&rejected, &fresh, "2026-10-03T09:00:00Z"),
+            [(OLD.to_owned(), Some(NEW.to_owned()))]
+        );
+    }
+
+    #[test]
+    fn matches_after_the_deadline_if_resubmitted_before_it() {
+        let rejected = [rejected("2026-10-05T12:00:00Z")];
+        let fresh = [submission(
+            NEW,
+            "2026-10-05T11:59:00Z",
+            "ada@example.com",
+            "Pocket Tetris",
+            None,
+        )];
+
+        assert_eq!(
+            outcome(&rejected, &fresh, "2026-10-06T00:00:00Z"),
+            [(OLD.to_owned(), Some(NEW.to_owned()))]
+        );
+    }
+
+    #[test]
+    fn expires_when_resubmitted_after_the_deadline() {
+        let rejected = [rejected("2026-10-05T12:00:00Z")];
+        let fresh = [submission(
+            NEW,
+            "2026-10-05T12:01:00Z",
+            "ada@example.com",
+            "Pocket Tetris",
+            None,
+        )];
+
+        assert_eq!(
+            outcome(&rejected, &fresh, "2026-10-06T00:00:00Z"),
+            [(OLD.to_owned(), None)]
+        );
+    }
+
+    #[test]
+    fn changed_name_is_not_a_resubmission() {
+        let rejected = [rejected("2026-10-05T12:00:00Z")];
+        let fresh = [submission(
+            NEW,
+            "2026-10-03T08:00:00Z",
+            "ada@example.com",
+            "Pocket Tetris 2",
+            None,
+        )];
+
+        assert_eq!(outcome(&rejected, &fresh, "2026-10-03T09:00:00Z"), []);
+        assert_eq!(
+            outcome(&rejected, &fresh, "2026-10-06T00:00:00Z"),
+            [(OLD.to_owned(), None)]
+        );
+    }
+
+    #[test]
+    fn ignores_records_created_before_the_rejection() {
+        // rejected on 2026-10-02T12:00:00Z, a day after it was submitted
+        let rejected = [rejected("2026-10-05T12:00:00Z")];
+
+        for created in [
+            "2026-09-30T08:00:00Z",
+            "2026-10-01T18:00:00Z",
+            "2026-10-02T12:00:00Z",
+        ] {
+            let fresh = [submission(
+                NEW,
+                created,
+                "ada@example.com",
+                "Pocket Tetris",
+                None,
+            )];
+
+            assert_eq!(
+                outcome(&rejected, &fresh, "2026-10-03T09:00:00Z"),
+                [],
+                "{created}"
+            );
+        }
+    }
+
+    #[test]
+    fn resubmits_each_record_once() {
+        let second = "recOld00000000002";
+        let rejected = [
+            rejected("2026-10-05T12:00:00Z"),
+            submission(
+                second,
+                "2026-10-01T12:30:00Z",
+                "ada@example.com",
+                "Pocket Tetris",
+                Some("2026-10-05T13:00:00Z"),
+            ),
+        ];
+        let fresh = [submission(
+            NEW,
+            "2026-10-03T08:00:00Z",
+            "ada@example.com",
+            "Pocket Tetris",
+            None,
+        )];
+
+        assert_eq!(
+            outcome(&rejected, &fresh, "2026-10-06T00:00:00Z"),
+            [
+                (OLD.to_owned(), Some(NEW.to_owned())),
+                (second.to_owned(), None)
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_records_without_a_deadline() {
+        let mut old = rejected("2026-10-05T12:00:00Z");
+        old.fields_mut().resubmit_deadline = None;
+
+        assert_eq!(outcome(&[old], &[], "2026-10-06T00:00:00Z"), []);
+    }
+
+    #[test]
+    fn deadline_is_three_days_after_the_decision() {
+        assert_eq!(
+            deadline_from(at("2026-10-01T12:00:00Z")),
+            at("2026-10-04T12:00:00Z")
+        );
+    }
+}
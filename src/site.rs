@@ -0,0 +1,169 @@
+use std::{collections::BTreeSet, fs, path::Path, sync::LazyLock};
+
+use saycheese_review::airtable::api::RecordId;
+use serde::Serialize;
+use tera::{Context, Tera};
+
+use crate::{error::AppError, export, manifest::Manifest};
+
+const INDEX_HTML: &str = include_str!("../static/gallery/index.html");
+const PROJECT_HTML: &str = include_str!("../static/gallery/project.html");
+const STYLE_CSS: &str = include_str!("../static/gallery/style.css");
+
+static TEMPLATES: LazyLock<Tera> = LazyLock::new(|| {
+    let mut tera = Tera::default();
+    tera.add_raw_templates([("index.html", INDEX_HTML), ("project.html", PROJECT_HTML)])
+        .expect("gallery templates should be valid");
+    tera
+});
+
+/// A project as listed on the front page.
+#[derive(Debug, Serialize)]
+struct Card {
+    id: RecordId,
+    name: String,
+    author: String,
+    os: String,
+    arch: String,
+    /// Image shown on the card, the full screenshot if there's no thumbnail.
+    thumbnail: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct SiteReport {
+    pub pages: usize,
+    /// Projects in the snapshot that were left out of the site, and why.
+    pub skipped: Vec<(RecordId, String)>,
+}
+
+/// Renders the gallery as a static site from an export directory.
+///
+/// `snapshot` is laid out the way [`export::export`] writes it, so the site
+/// can be rebuilt offline as often as needed. The site is written to `out`
+/// with a filterable front page and a page for each project at `projects/<record id>/`.
+pub fn generate(snapshot: &Path, out: &Path) -> Result<SiteReport, AppError> {
+    fs::create_dir_all(out)?;
+
+    let mut report = SiteReport::default();
+    let mut cards = Vec::new();
+
+    for entry in export::read_index(snapshot)? {
+        let manifest = match read_manifest(&snapshot.join(&entry.path)) {
+            Ok(manifest) => manifest,
+            Err(err) => {
+                tracing::warn!("leaving {} out of the gallery: {err}", entry.id);
+                report.skipped.push((entry.id, err));
+                continue;
+            }
+        };
+
+        let project_dir = out.join("projects").join(entry.id.to_string());
+        fs::create_dir_all(&project_dir)?;
+
+        let mut files = vec![manifest.qr.image.clone(), manifest.demo.clone()];
+        files.extend(entry.thumbnail.clone());
+        for file in files {
+            fs::copy(
+                snapshot.join(&entry.path).join(&file),
+                project_dir.join(&file),
+            )?;
+        }
+
+        let mut context = Context::new();
+        context.insert("project", &manifest);
+        fs::write(
+            project_dir.join("index.html"),
+            TEMPLATES.render("project.html", &context)?,
+        )?;
+        report.pages += 1;
+
+        cards.push(Card {
+            id: entry.id,
+            name: manifest.name,
+            author: manifest.author,
+            os: manifest.os.trim().to_owned(),
+            arch: manifest.arch.trim().to_owned(),
+            thumbnail: entry.thumbnail.unwrap_or(manifest.demo),
+        });
+    }
+
+    cards.sort_by_cached_key(|card| card.name.to_lowercase());
+
+    let oses: BTreeSet<&str> = cards.iter().map(|card| card.os.as_str()).collect();
+    let arches: BTreeSet<&str> = cards.iter().map(|card| card.arch.as_str()).collect();
+
+    let mut context = Context::new();
+    context.insert("projects", &cards);
+    context.insert("oses", &oses);
+    context.insert("arches", &arches);
+    fs::write(
+        out.join("index.html"),
+        TEMPLATES.render("index.html", &context)?,
+    )?;
+    fs::write(out.join("style.css"), STYLE_CSS)?;
+    report.pages += 1;
+
+    Ok(report)
+}
+
+/// Reads and validates an exported project's `proj.json`.
+fn read_manifest(dir: &Path) -> Result<Manifest, String> {
+    let manifest = fs::read(dir.join("proj.json"))
+        .map_err(|err| format!("unable to read proj.json: {err}"))?;
+    let manifest: Manifest = serde_json::from_slice(&manifest)
+        .map_err(|err| format!("unable to parse proj.json: {err}"))?;
+
+    manifest.validate().map_err(|errors| {
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        format!("invalid proj.json: {}", errors.join(", "))
+    })?;
+
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn render_project(ty: &str, url: &str) -> String {
+        let manifest: Manifest = serde_json::from_value(json!({
+            "version": 1,
+            "name": "Cheese Grater",
+            "author": "Kestrel",
+            "qr": { "image": "qr.png", "type": ty, "url": url },
+            "demo": "proj.png",
+            "os": "Linux",
+            "arch": "x86_64",
+            "description": "Grates cheese.",
+            "repo": "https://github.com/hackclub/say-cheese",
+        }))
+        .unwrap();
+
+        let mut context = Context::new();
+        context.insert("project", &manifest);
+        TEMPLATES.render("project.html", &context).unwrap()
+    }
+
+    #[test]
+    fn links_http_payloads() {
+        let html = render_project("URL", "https://example.com/project");
+        assert!(html.contains(r#"<a href="https:&#x2F;&#x2F;example.com&#x2F;project">"#));
+    }
+
+    #[test]
+    fn never_links_other_payloads() {
+        for (ty, url) in [
+            ("URL", "javascript:alert(1)"),
+            ("URL", "data:text/html,<script>alert(1)</script>"),
+            ("TEXT", "javascript:alert(1)"),
+            ("TEXT", "data:text/html;base64,PGgxPmhpPC9oMT4="),
+        ] {
+            let html = render_project(ty, url);
+            assert!(!html.contains("href=\"javascript"), "{url}");
+            assert!(!html.contains("href=\"data"), "{url}");
+            assert!(!html.contains("<script>"), "{url}");
+        }
+    }
+}
@@ -0,0 +1,300 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use saycheese_review::airtable::api::RecordId;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    audit::{AuditAction, AuditEntry, AuditLog},
+    error::AppError,
+    mirror::Store,
+    status::Status,
+};
+
+/// How long computed statistics are served before they are computed again.
+pub const STATS_TTL: Duration = Duration::from_secs(60);
+
+/// Where the review queue stands. Durations are in seconds.
+#[derive(Debug, Clone, Serialize)]
+pub struct Stats {
+    pub generated_at: DateTime<Utc>,
+    pub total: usize,
+    pub by_status: BTreeMap<String, usize>,
+    pub by_os: BTreeMap<String, usize>,
+    pub by_architecture: BTreeMap<String, usize>,
+    /// Reviews made through this server by each reviewer, not counting ones that were undone.
+    pub reviewers: BTreeMap<String, ReviewerStats>,
+    /// Median time from a submission being created to its review.
+    pub median_secs_to_decision: Option<i64>,
+    pub backlog: BacklogStats,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReviewerStats {
+    pub reviews: usize,
+    pub accepted: usize,
+    pub rejected: usize,
+    /// Share of reviews that were acceptances, from 0 to 1.
+    pub accept_rate: f64,
+    pub per_day: BTreeMap<NaiveDate, usize>,
+}
+
+/// How long the submissions waiting for a review have been waiting.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BacklogStats {
+    pub waiting: usize,
+    pub p50_age_secs: Option<i64>,
+    pub p90_age_secs: Option<i64>,
+    pub p99_age_secs: Option<i64>,
+    pub oldest_age_secs: Option<i64>,
+}
+
+/// Nearest-rank percentile of sorted values.
+fn percentile(sorted: &[i64], p: f64) -> Option<i64> {
+    if sorted.is_empty() {
+        return None;
+    }
+
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+fn count<'a>(values: impl Iterator<Item = &'a str>) -> BTreeMap<String, usize> {
+    let mut counts = BTreeMap::new();
+    for value in values {
+        *counts.entry(value.to_owned()).or_default() += 1;
+    }
+    counts
+}
+
+/// Reviews that are still in effect, oldest first.
+///
+/// A review is dropped when it was undone, and replaced when the submission was reviewed again.
+fn effective_reviews(mut entries: Vec<AuditEntry>) -> Vec<AuditEntry> {
+    entries.sort_by_key(|entry| entry.id);
+
+    let mut reviews: HashMap<RecordId, AuditEntry> = HashMap::new();
+
+    for entry in entries {
+        match entry.action {
+            AuditAction::Review => {
+                reviews.insert(entry.record_id.clone(), entry);
+            }
+            AuditAction::Undo => {
+                reviews.remove(&entry.record_id);
+            }
+            _ => {}
+        }
+    }
+
+    let mut reviews: Vec<AuditEntry> = reviews.into_values().collect();
+    reviews.sort_by_key(|entry| entry.id);
+    reviews
+}
+
+/// Computes statistics for every submission in the store and every review in the audit log.
+pub async fn compute(store: &Store, audit: &AuditLog) -> Result<Stats, AppError> {
+    let now = Utc::now();
+    let records = store.all().await?;
+
+    let created: HashMap<&RecordId, DateTime<Utc>> = records
+        .iter()
+        .map(|rec| (rec.id(), rec.created_time()))
+        .collect();
+
+    let mut reviewers: BTreeMap<String, ReviewerStats> = BTreeMap::new();
+    let mut decision_times = Vec::new();
+
+    for review in effective_reviews(audit.list(None)?) {
+        let stats = reviewers.entry(review.reviewer.clone()).or_default();
+        stats.reviews += 1;
+        *stats.per_day.entry(review.at.date_naive()).or_default() += 1;
+
+        match review.changes.get("status").map(|change| &change.new) {
+            Some(Value::String(status)) if status == Status::Accepted.as_str() => {
+                stats.accepted += 1
+            }
+            Some(Value::String(status)) if status == Status::Rejected.as_str() => {
+                stats.rejected += 1
+            }
+            _ => {}
+        }
+
+        if let Some(created) = created.get(&review.record_id) {
+            decision_times.push((review.at - *created).num_seconds());
+        }
+    }
+
+    for stats in reviewers.values_mut() {
+        stats.accept_rate = stats.accepted as f64 / stats.reviews as f64;
+    }
+    decision_times.sort_unstable();
+
+    let mut ages: Vec<i64> = records
+        .iter()
+        .filter(|rec| matches!(rec.fields().status, Status::New | Status::Resubmitted))
+        .map(|rec| (now - rec.created_time()).num_seconds())
+        .collect();
+    ages.sort_unstable();
+
+    Ok(Stats {
+        generated_at: now,
+        total: records.len(),
+        by_status: count(records.iter().map(|rec| rec.fields().status.as_str())),
+        by_os: count(records.iter().map(|rec| rec.fields().os.as_str())),
+        by_architecture: count(records.iter().map(|rec| rec.fields().architecture.as_str())),
+        reviewers,
+        median_secs_to_decision: percentile(&decision_times, 0.5),
+        backlog: BacklogStats {
+            waiting: ages.len(),
+            p50_age_secs: percentile(&ages, 0.5),
+            p90_age_secs: percentile(&ages, 0.9),
+            p99_age_secs: percentile(&ages, 0.99),
+            oldest_age_secs: ages.last().copied(),
+        },
+    })
+}
+
+/// The last computed [`Stats`], reused for [`STATS_TTL`].
+#[derive(Default)]
+pub struct StatsCache {
+    cached: Mutex<Option<(Instant, Stats)>>,
+}
+
+impl StatsCache {
+    fn cached(&self) -> MutexGuard<'_, Option<(Instant, Stats)>> {
+        self.cached
+            .lock()
+            .expect("stats lock should not be poisoned")
+    }
+
+    pub async fn get(&self, store: &Store, audit: &AuditLog) -> Result<Stats, AppError> {
+        if let Some((at, stats)) = &*self.cached() {
+            if at.elapsed() < STATS_TTL {
+                return Ok(stats.clone());
+            }
+        }
+
+        let stats = compute(store, audit).await?;
+        *self.cached() = Some((Instant::now(), stats.clone()));
+
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearest_rank_percentiles() {
+        let hundred: Vec<i64> = (1..=100).collect();
+
+        for (sorted, p, expected) in [
+            (&[][..], 0.5, None),
+            (&[][..], 0.99, None),
+            (&[7][..], 0.0, Some(7)),
+            (&[7][..], 0.5, Some(7)),
+            (&[7][..], 0.99, Some(7)),
+            (&[1, 2][..], 0.5, Some(1)),
+            (&[1, 2][..], 0.51, Some(2)),
+            (&[1, 2, 3, 4][..], 0.5, Some(2)),
+            (&[1, 2, 3, 4][..], 0.0, Some(1)),
+            (&[1, 2, 3, 4][..], 1.0, Some(4)),
+            (&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10][..], 0.9, Some(9)),
+            (&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10][..], 0.99, Some(10)),
+            (&hundred[..], 0.99, Some(99)),
+            (&hundred[..], 0.5, Some(50)),
+        ] {
+            assert_eq!(percentile(sorted, p), expected, "p{p} of {sorted:?}");
+        }
+    }
+
+    fn entry(id: i64, record: &str, action: AuditAction) -> AuditEntry {
+        AuditEntry {
+            id,
+            at: Utc::now(),
+            reviewer: "ada".to_owned(),
+            client_ip: None,
+            record_id: record.parse().unwrap(),
+            action,
+            changes: Default::default(),
+        }
+    }
+
+    const A: &str = "recA1b2C3d4E5f6G7";
+    const B: &str = "recH8i9J0k1L2m3N4";
+
+    #[test]
+    fn keeps_reviews_in_effect() {
+        for (entries, expected) in [
+            (vec![], vec![]),
+            (vec![entry(1, A, AuditAction::Review)], vec![1]),
+            // undone
+            (
+                vec![
+                    entry(1, A, AuditAction::Review),
+                    entry(2, A, AuditAction::Undo),
+                ],
+                vec![],
+            ),
+            // undone, then reviewed again
+            (
+                vec![
+                    entry(1, A, AuditAction::Review),
+                    entry(2, A, AuditAction::Undo),
+                    entry(3, A, AuditAction::Review),
+                ],
+                vec![3],
+            ),
+            // reviewed again after a resubmission replaces the first review
+            (
+                vec![
+                    entry(1, A, AuditAction::Review),
+                    entry(2, A, AuditAction::Resubmission),
+                    entry(3, A, AuditAction::Review),
+                ],
+                vec![3],
+            ),
+            // an undo only affects its own record
+            (
+                vec![
+                    entry(1, A, AuditAction::Review),
+                    entry(2, B, AuditAction::Review),
+                    entry(3, B, AuditAction::Undo),
+                ],
+                vec![1],
+            ),
+            // read in id order, whatever order the log returns
+            (
+                vec![
+                    entry(4, B, AuditAction::Review),
+                    entry(3, A, AuditAction::Undo),
+                    entry(2, A, AuditAction::Review),
+                    entry(1, B, AuditAction::Review),
+                ],
+                vec![4],
+            ),
+            // bookkeeping isn't a review
+            (
+                vec![
+                    entry(1, A, AuditAction::EmailDelivery),
+                    entry(2, B, AuditAction::Expire),
+                    entry(3, B, AuditAction::Export),
+                ],
+                vec![],
+            ),
+        ] {
+            let ids: Vec<i64> = effective_reviews(entries.clone())
+                .iter()
+                .map(|entry| entry.id)
+                .collect();
+            assert_eq!(ids, expected, "{entries:?}");
+        }
+    }
+}
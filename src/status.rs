@@ -0,0 +1,152 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Where a submission is in the review lifecycle.
+///
+/// This is stored in the `status` single select field in Airtable,
+/// so the serialized names have to match the options defined there.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    #[default]
+    New,
+    InReview,
+    Accepted,
+    Rejected,
+    /// A rejected submission whose resubmission window closed, or was used by a resubmission.
+    RejectedFinal,
+    Resubmitted,
+    Shipped,
+}
+
+/// Every transition a submission is allowed to make.
+/// Anything not listed here is rejected by [`Status::transition`].
+///
+/// Reviewers decide straight from the queue, and claims only live in memory,
+/// so a `new` submission can be accepted or rejected without going through `in_review`.
+const TRANSITIONS: &[(Status, Status)] = &[
+    (Status::New, Status::InReview),
+    (Status::New, Status::Accepted),
+    (Status::New, Status::Rejected),
+    (Status::New, Status::Resubmitted),
+    (Status::InReview, Status::Accepted),
+    (Status::InReview, Status::Rejected),
+    (Status::Rejected, Status::RejectedFinal),
+    (Status::Resubmitted, Status::Accepted),
+    (Status::Resubmitted, Status::Rejected),
+    (Status::Accepted, Status::Shipped),
+];
+
+#[derive(Debug, Clone, Copy, Error)]
+#[error("cannot move a submission from `{from}` to `{to}`")]
+pub struct InvalidTransition {
+    pub from: Status,
+    pub to: Status,
+}
+
+impl Status {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Status::New => "new",
+            Status::InReview => "in_review",
+            Status::Accepted => "accepted",
+            Status::Rejected => "rejected",
+            Status::RejectedFinal => "rejected_final",
+            Status::Resubmitted => "resubmitted",
+            Status::Shipped => "shipped",
+        }
+    }
+
+    pub fn can_transition(self, to: Status) -> bool {
+        TRANSITIONS.contains(&(self, to))
+    }
+
+    /// Returns the new status if moving from `self` to `to` is allowed.
+    pub fn transition(self, to: Status) -> Result<Status, InvalidTransition> {
+        if self.can_transition(to) {
+            Ok(to)
+        } else {
+            Err(InvalidTransition { from: self, to })
+        }
+    }
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Status; 7] = [
+        Status::New,
+        Status::InReview,
+        Status::Accepted,
+        Status::Rejected,
+        Status::RejectedFinal,
+        Status::Resubmitted,
+        Status::Shipped,
+    ];
+
+    #[test]
+    fn allows_listed_transitions() {
+        for (from, to, allowed) in [
+            (Status::New, Status::InReview, true),
+            (Status::New, Status::Resubmitted, true),
+            (Status::New, Status::Accepted, true),
+            (Status::New, Status::Rejected, true),
+            (Status::New, Status::RejectedFinal, false),
+            (Status::New, Status::Shipped, false),
+            (Status::InReview, Status::Accepted, true),
+            (Status::InReview, Status::Rejected, true),
+            (Status::InReview, Status::New, false),
+            (Status::Rejected, Status::Resubmitted, false),
+            (Status::Rejected, Status::RejectedFinal, true),
+            (Status::Rejected, Status::Accepted, false),
+            (Status::RejectedFinal, Status::Resubmitted, false),
+            (Status::Resubmitted, Status::Accepted, true),
+            (Status::Resubmitted, Status::Rejected, true),
+            (Status::Resubmitted, Status::Shipped, false),
+            (Status::Accepted, Status::Shipped, true),
+            (Status::Accepted, Status::Rejected, false),
+            (Status::Shipped, Status::Accepted, false),
+            (Status::Accepted, Status::Accepted, false),
+        ] {
+            assert_eq!(from.can_transition(to), allowed, "{from} -> {to}");
+        }
+    }
+
+    #[test]
+    fn transition_matches_table() {
+        for from in ALL {
+            for to in ALL {
+                match from.transition(to) {
+                    Ok(status) => {
+                        assert!(TRANSITIONS.contains(&(from, to)), "{from} -> {to}");
+                        assert_eq!(status, to);
+                    }
+                    Err(err) => {
+                        assert!(!TRANSITIONS.contains(&(from, to)), "{from} -> {to}");
+                        assert_eq!((err.from, err.to), (from, to));
+                        assert_eq!(
+                            err.to_string(),
+                            format!("cannot move a submission from `{from}` to `{to}`")
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn final_statuses_are_final() {
+        for from in [Status::RejectedFinal, Status::Shipped] {
+            assert!(ALL.iter().all(|&to| from.transition(to).is_err()), "{from}");
+        }
+    }
+}
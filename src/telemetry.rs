@@ -0,0 +1,203 @@
+use std::{
+    io::{self, Write},
+    time::Instant,
+};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderName, HeaderValue},
+    middleware::Next,
+};
+use tracing::Instrument;
+use tracing_subscriber::{fmt::MakeWriter, EnvFilter};
+use uuid::Uuid;
+
+use crate::{
+    config::{Config, LogFormat},
+    AIRTABLE_API_KEY,
+};
+
+/// Header that carries the request ID. One sent by a proxy is kept, otherwise a new one is made.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest request ID that is accepted from a client.
+const MAX_REQUEST_ID_LENGTH: usize = 64;
+
+/// Keys shorter than this are placeholders, and replacing them would mangle unrelated text.
+const MIN_REDACTED_KEY_LENGTH: usize = 16;
+
+/// Sets up `tracing` with the filter and format from the config.
+///
+/// Logs from dependencies that use `log` are forwarded as well.
+pub fn init(config: &Config) -> Result<(), String> {
+    let filter = EnvFilter::try_new(&config.log_filter)
+        .map_err(|err| format!("`LOG_LEVEL` is not a valid filter: {err}"))?;
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(Redacting);
+
+    let res = match config.log_format {
+        LogFormat::Pretty => builder.pretty().try_init(),
+        LogFormat::Json => builder.json().with_current_span(true).try_init(),
+    };
+
+    res.map_err(|err| err.to_string())
+}
+
+/// Hides the Airtable API key and email addresses in `text`.
+pub fn redact(text: &str) -> String {
+    let text = match AIRTABLE_API_KEY.len() >= MIN_REDACTED_KEY_LENGTH {
+        true => text.replace(AIRTABLE_API_KEY, "[api key]"),
+        false => text.to_owned(),
+    };
+
+    redact_emails(&text)
+}
+
+fn is_local_part(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "._%+-".contains(c)
+}
+
+fn is_domain(c: char) -> bool {
+    c.is_ascii_alphanumeric() || ".-".contains(c)
+}
+
+/// Replaces anything shaped like `local@domain.tld` with `[email]`.
+fn redact_emails(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(at) = rest.find('@') {
+        let local_start = rest[..at]
+            .char_indices()
+            .rev()
+            .take_while(|&(_, c)| is_local_part(c))
+            .last()
+            .map_or(at, |(i, _)| i);
+        let domain_len = rest[at + 1..]
+            .char_indices()
+            .take_while(|&(_, c)| is_domain(c))
+            .last()
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        let domain = rest[at + 1..at + 1 + domain_len].trim_end_matches('.');
+
+        if local_start < at && domain.contains('.') {
+            out.push_str(&rest[..local_start]);
+            out.push_str("[email]");
+            rest = &rest[at + 1 + domain.len()..];
+        } else {
+            out.push_str(&rest[..=at]);
+            rest = &rest[at + 1..];
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+/// Writes each log line to stderr once it's complete, after passing it through [`redact`].
+struct Redacting;
+
+struct RedactingWriter(Vec<u8>);
+
+impl<'a> MakeWriter<'a> for Redacting {
+    type Writer = RedactingWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter(Vec::new())
+    }
+}
+
+impl Write for RedactingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for RedactingWriter {
+    fn drop(&mut self) {
+        let line = redact(&String::from_utf8_lossy(&self.0));
+        // there's nowhere left to report a failure to write logs
+        let _ = io::stderr().write_all(line.as_bytes());
+    }
+}
+
+fn request_id(req: &ServiceRequest) -> String {
+    req.headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|id| id.to_str().ok())
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= MAX_REQUEST_ID_LENGTH
+                && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+        .map(ToOwned::to_owned)
+        .unwrap_or_else(|| Uuid::new_v4().to_string())
+}
+
+/// Middleware that runs every request in a span with its ID, logs how it went,
+/// and returns the ID in [`REQUEST_ID_HEADER`].
+///
+/// Anything logged while handling the request, including Airtable calls, is inside the span.
+pub async fn trace_request(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let request_id = request_id(&req);
+    let span = tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %req.method(),
+        path = %req.path(),
+    );
+
+    let started = Instant::now();
+    let mut res = next.call(req).instrument(span.clone()).await?;
+
+    span.in_scope(|| {
+        tracing::info!(
+            status = res.status().as_u16(),
+            duration_ms = started.elapsed().as_secs_f64() * 1000.0,
+            "handled request"
+        );
+    });
+
+    res.headers_mut().insert(
+        HeaderName::from_static(REQUEST_ID_HEADER),
+        HeaderValue::from_str(&request_id).expect("request IDs should be valid header values"),
+    );
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_emails() {
+        for (text, redacted) in [
+            ("", ""),
+            ("no addresses here", "no addresses here"),
+            ("contact ada@example.com now", "contact [email] now"),
+            ("ada@example.com.", "[email]."),
+            ("a@b.co,x@y.org", "[email],[email]"),
+            ("email=ada+tag@mail.example.co.uk&x=1", "email=[email]&x=1"),
+            (r#"{"Email":"ada@example.com"}"#, r#"{"Email":"[email]"}"#),
+            ("→ ada@example.com ←", "→ [email] ←"),
+            ("@example.com", "@example.com"),
+            ("ada@localhost", "ada@localhost"),
+            ("ada@", "ada@"),
+            ("ada@@example.com", "ada@@example.com"),
+            ("version 1.2@3", "version 1.2@3"),
+        ] {
+            assert_eq!(redact_emails(text), redacted, "{text:?}");
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use chrono::{TimeDelta, Utc};
+use saycheese_review::airtable::api::RecordId;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    audit::{self, Actor, AuditAction, AuditLog, Changes},
+    error::AppError,
+    mirror::Store,
+    outbox::Outbox,
+    Submission,
+};
+
+/// How long after a review it can still be undone.
+pub const UNDO_WINDOW: TimeDelta = TimeDelta::minutes(15);
+
+/// Fields that a review sets, and that an undo puts back.
+const REVIEW_FIELDS: [&str; 3] = ["status", "email_message", "resubmit_deadline"];
+
+/// Fields that must still hold what the review set for it to be undone.
+/// `resubmit_deadline` isn't compared since Airtable rounds it to the millisecond.
+const CHECKED_FIELDS: [&str; 2] = ["status", "email_message"];
+
+#[derive(Debug, Serialize)]
+pub struct UndoReport {
+    /// The fields that were put back.
+    pub restored: Changes,
+    /// Decision emails that were still queued and won't be sent.
+    pub cancelled_emails: usize,
+    /// Decision emails that had already gone out, so the submitter needs a follow up.
+    pub sent_emails: usize,
+}
+
+/// Reverts the last review of a submission, if it happened within [`UNDO_WINDOW`].
+///
+/// The previous values come from the review's [`audit`] entry, and the status is restored
+/// directly rather than through [`Status::transition`](crate::status::Status::transition)
+/// since it's going back to where it was. Any decision email still in the outbox is cancelled.
+pub async fn undo_review(
+    id: &RecordId,
+    store: &Store,
+    audit: &AuditLog,
+    outbox: &Outbox,
+    actor: &Actor,
+) -> Result<UndoReport, AppError> {
+    let entry = audit
+        .last_change(id)?
+        .ok_or_else(|| AppError::NotFound(format!("{id} has not been reviewed")))?;
+
+    if entry.action != AuditAction::Review {
+        return Err(AppError::Conflict(format!(
+            "the last change to {id} was not a review, so there's nothing to undo"
+        )));
+    }
+
+    if Utc::now() - entry.at > UNDO_WINDOW {
+        return Err(AppError::Conflict(format!(
+            "reviews can only be undone within {} minutes",
+            UNDO_WINDOW.num_minutes()
+        )));
+    }
+
+    let rec = store.fetch(id).await?;
+
+    let Value::Object(mut fields) = serde_json::to_value(rec.fields())? else {
+        unreachable!("submissions should serialize to an object");
+    };
+
+    for field in CHECKED_FIELDS {
+        let Some(change) = entry.changes.get(field) else {
+            continue;
+        };
+
+        if fields.get(field).unwrap_or(&Value::Null) != &change.new {
+            return Err(AppError::Conflict(format!(
+                "`{field}` on {id} has changed since it was reviewed"
+            )));
+        }
+    }
+
+    for field in REVIEW_FIELDS {
+        if let Some(change) = entry.changes.get(field) {
+            fields.insert(field.to_owned(), change.old.clone());
+        }
+    }
+
+    let data: Submission = serde_json::from_value(Value::Object(fields))?;
+    let changes = audit::diff(Some(rec.fields()), &data)?;
+
+    // cancelled before the revert, so the outbox worker can't send it in the meantime
+    let cancelled_emails = outbox.cancel_pending(entry.id)?;
+    let sent_emails = outbox.sent_for(entry.id)?;
+
+    store.update(id, &changes).await?;
+    audit.record(actor, id, AuditAction::Undo, &changes)?;
+
+    if sent_emails > 0 {
+        tracing::warn!("undid the review of {id}, but its decision email was already sent");
+    }
+
+    Ok(UndoReport {
+        restored: changes,
+        cancelled_emails,
+        sent_emails,
+    })
+}